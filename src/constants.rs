@@ -1,14 +1,38 @@
 #![allow(dead_code)]
-use crate::render::{LightingConfig, ReflectionConstants};
+use crate::{
+    picture::{DepthFunc, DepthTest, LineStyle},
+    render::{LightingConfig, Projection, ReflectionConstants, Viewport},
+};
 
 /* CONFIG */
 pub const DEFAULT_SCRIPT: &str = "scripts/stonehenge.mdl";
 pub const DEFAULT_PICTURE_DIMENSIONS: (usize, usize) = (500, 500);
+// space left around the scene by frame_all, as a fraction of the picture
+pub const DEFAULT_FRAME_MARGIN: f32 = 0.05;
+// degrees from the x axis that depth is drawn at for cabinet and cavalier projections
+pub const DEFAULT_OBLIQUE_ANGLE: f32 = 45.0;
+// world units that fill the picture, see projection.rs
+pub const DEFAULT_VIEWPORT: Viewport = Viewport { xmin: 0.0, ymin: 0.0, xmax: 500.0, ymax: 500.0 };
 pub const DEFAULT_BACKGROUND_COLOR: (usize, usize, usize) = WHITE;
 pub const DEFAULT_FOREGROUND_COLOR: (usize, usize, usize) = BLUE;
 pub const PARAMETRIC_STEPS: i32 = 50;
 pub const ENABLE_BACK_FACE_CULLING: bool = true;
-pub const ENABLE_Z_BUFFER: bool = true;
+// rows in each band the picture is split into for drawing polygons on several threads
+pub const TILE_ROWS: usize = 16;
+// anything with fewer triangles than this isn't worth starting threads for
+pub const PARALLEL_TRIANGLES: usize = 256;
+// how many different meshes and how many different spheres and tori get kept around for the next frame
+pub const GEOMETRY_CACHE_SIZE: usize = 64;
+pub const DEFAULT_DEPTH_TEST: DepthTest = DepthTest {
+    func: DepthFunc::LessEqual,
+    write: true,
+    bias: 0.0,
+    slope_bias: 0.0,
+};
+pub const DEFAULT_LINE_STYLE: LineStyle = LineStyle {
+    width: 1.0,
+    antialias: false,
+};
 pub const DEFAULT_LIGHTING_CONFIG: LightingConfig = LightingConfig {
     ambient_light_color: [50.0, 50.0, 50.0],
     point_light_color: [255.0, 255.0, 255.0],
@@ -19,18 +43,21 @@ pub const DEFAULT_REFLECTION_CONSTANTS: ReflectionConstants = ReflectionConstant
     diffuse: [0.75, 0.25, 0.25],
     specular: [0.25, 0.25, 0.75],
 };
-#[derive(Clone, Copy, Debug)]
-pub enum ShadingMode {
-    Wireframe,
-    FlatRandom,
-    Flat,
-    Gouraud,
-    Phong,
-}
-pub const DEFAULT_SHADING_MODE: ShadingMode = ShadingMode::Flat;
+// a name the shading command takes, see render/shader.rs
+pub const DEFAULT_SHADING: &str = "flat";
+// for shading with edges, how far towards the eye the edges get pulled so they're drawn over their own faces
+pub const DEFAULT_EDGE_BIAS: f32 = 1.0;
+// and how many pixels' worth of the face's depth slope on top of that, plus half the line width (see polygon_list.rs)
+pub const EDGE_SLOPE_BIAS: f32 = 1.0;
+pub const DEFAULT_EDGE_COLOR: (usize, usize, usize) = BLACK;
+pub const DEFAULT_PROJECTION: Projection = Projection::Orthographic;
 pub const SPECULAR_EXPONENT: f32 = 4.0;
-pub const GENERATE_TEMPORARY_FRAME_FILES: bool = true;
+pub const DEFAULT_ANIMATION_EXTENSION: &str = "gif";
 pub const DEFAULT_ANIMATION_DELAY_MS: u32 = 20; // for some reason when this is set to 10 ms it becomes really slow
+// apng stores delays as a u16 number of ms
+pub const MAX_ANIMATION_DELAY_MS: f32 = u16::MAX as f32;
+// gifs store the loops after the first play as a u16
+pub const MAX_ANIMATION_PLAYS: u32 = u16::MAX as u32 + 1;
 
 /* COLORS */
 pub const WHITE: (usize, usize, usize) = (255, 255, 255);
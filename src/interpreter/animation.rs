@@ -2,36 +2,106 @@ use super::parser::Command;
 use std::{
     error::Error,
     collections::HashMap,
-    fs::File
+    f32::consts::PI,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
 };
-use image::{ImageBuffer, RgbaImage, Frame, Delay, codecs::gif::{GifEncoder, Repeat}};
 
-pub fn first_pass(commands: &Vec<Command>) -> Result<(usize, String), Box<dyn Error>> {
+use crate::{
+    constants::{DEFAULT_ANIMATION_DELAY_MS, DEFAULT_ANIMATION_EXTENSION, MAX_ANIMATION_DELAY_MS, MAX_ANIMATION_PLAYS},
+    picture::Picture,
+};
+
+pub struct AnimationSettings {
+    pub num_frames: usize,
+    pub basename: String,
+    // how long each frame is held for in milliseconds
+    pub default_delay: f32,
+    pub delays: Vec<f32>,
+    // number of times the animation plays, 0 loops forever
+    pub plays: u32,
+    // renders per frame and how much of the time until the next frame they're spread over
+    pub motion_blur: Option<(usize, f32)>,
+}
+
+pub fn first_pass(commands: &Vec<Command>) -> Result<AnimationSettings, Box<dyn Error>> {
     let mut frames: usize = 0;
     let mut basename = String::new();
+    let mut default_delay = DEFAULT_ANIMATION_DELAY_MS as f32;
+    let mut plays = 0;
+    let mut motion_blur = None;
 
     let mut contains_frames = false;
     let mut contains_vary = false;
     let mut contains_basename = false;
     let mut contains_tween = false;
 
+    // frame specific delays have to wait until we know how many frames there are
+    let mut holds: Vec<(usize, usize, f32)> = vec![];
+
     for command in commands {
         match command {
             Command::SetBaseName { name } => { basename = name.clone(); contains_basename = true; }
             Command::Tween { .. } => { contains_tween = true; }
             Command::SetFrames { num_frames } => { frames = *num_frames; contains_frames = true; }
-            Command::VaryKnob { .. } => { contains_vary = true; }
+            Command::VaryKnob { .. } | Command::Oscillate { .. } | Command::Wiggle { .. } => { contains_vary = true; }
+            Command::SetFps { fps } => {
+                if *fps <= 0.0 {
+                    return Err(format!("Fps has to be positive: {}.", fps).into());
+                }
+
+                default_delay = 1000.0 / fps;
+                check_delay(default_delay)?;
+            }
+            Command::SetDelay { delay_ms, start_frame, end_frame } => {
+                check_delay(*delay_ms)?;
+
+                match (start_frame, end_frame) {
+                    (Some(start_frame), Some(end_frame)) => holds.push((*start_frame, *end_frame, *delay_ms)),
+                    _ => default_delay = *delay_ms,
+                }
+            }
+            Command::SetLoop { plays: count } => {
+                // gifs can't store any more than this, see Animation::new
+                if *count > MAX_ANIMATION_PLAYS {
+                    return Err(format!("Loop count can't be more than {}: {}.", MAX_ANIMATION_PLAYS, count).into());
+                }
+
+                plays = *count;
+            }
+            Command::SetMotionBlur { samples, shutter } => {
+                if *samples == 0 {
+                    return Err("Motion blur needs at least one sample.".into());
+                }
+
+                if *shutter < 0.0 {
+                    return Err(format!("Motion blur shutter can't be negative: {}.", shutter).into());
+                }
+
+                motion_blur = Some((*samples, *shutter));
+            }
             _ => {}
         }
     }
 
     if (contains_vary || contains_tween || contains_basename) && !contains_frames {
-        Err("Animation was detected but the number of frames wasn't set.".into())
+        return Err("Animation was detected but the number of frames wasn't set.".into());
     } else if contains_frames && !contains_basename {
-        Err("Number of frames was set but basename wasn't.".into())
-    } else {
-        Ok((frames, basename))
+        return Err("Number of frames was set but basename wasn't.".into());
     }
+
+    let mut delays = vec![default_delay; frames];
+
+    for (start_frame, end_frame, delay_ms) in holds {
+        check_frame_range("Delay", start_frame, end_frame, frames)?;
+
+        for delay in &mut delays[start_frame..=end_frame] {
+            *delay = delay_ms;
+        }
+    }
+
+    Ok(AnimationSettings { num_frames: frames, basename, default_delay, delays, plays, motion_blur })
 }
 
 pub fn second_pass(commands: &Vec<Command>, frames: &usize) -> Result<Vec<HashMap<String, f32>>, Box<dyn Error>> {
@@ -41,13 +111,7 @@ pub fn second_pass(commands: &Vec<Command>, frames: &usize) -> Result<Vec<HashMa
     for command in commands {
         match command {
             Command::VaryKnob { knob, start_frame, end_frame, start_val, end_val } => {
-                if *start_frame >= *frames || *end_frame >= *frames {
-                    return Err(format!("Vary command has frames outside range: {} to {}.", start_frame, end_frame).into());
-                }
-                
-                if start_frame > end_frame {
-                    return Err(format!("Vary command has start_frame > end_frame: {} > {}.", start_frame, end_frame).into());
-                }
+                check_frame_range("Vary", *start_frame, *end_frame, *frames)?;
 
                 let num_frames = (end_frame - start_frame) as f32;
                 let delta = (end_val - start_val) / num_frames;
@@ -58,6 +122,29 @@ pub fn second_pass(commands: &Vec<Command>, frames: &usize) -> Result<Vec<HashMa
                 }
             }
 
+            Command::Oscillate { knob, start_frame, end_frame, period, amplitude, phase, waveform } => {
+                check_frame_range("Oscillate", *start_frame, *end_frame, *frames)?;
+
+                if *period <= 0.0 {
+                    return Err(format!("Oscillate command needs a positive period: {}.", period).into());
+                }
+
+                for (offset, knobs) in frame_knobs[*start_frame..=*end_frame].iter_mut().enumerate() {
+                    // phase is measured in cycles so 0.25 starts a sine wave at its peak
+                    let cycles = offset as f32 / period + phase;
+                    knobs.insert(knob.clone(), amplitude * waveform.sample(cycles));
+                }
+            }
+
+            Command::Wiggle { knob, start_frame, end_frame, frequency, amplitude, seed } => {
+                check_frame_range("Wiggle", *start_frame, *end_frame, *frames)?;
+
+                for (offset, knobs) in frame_knobs[*start_frame..=*end_frame].iter_mut().enumerate() {
+                    let x = offset as f32 * frequency;
+                    knobs.insert(knob.clone(), amplitude * smooth_noise(x, *seed));
+                }
+            }
+
             Command::SaveKnobList { name } => {
                 if !frame_knobs.is_empty() {
                     saved_knobs.insert(name.clone(), frame_knobs[0].clone());
@@ -65,13 +152,7 @@ pub fn second_pass(commands: &Vec<Command>, frames: &usize) -> Result<Vec<HashMa
             }
 
             Command::Tween { start_frame, end_frame, knoblist0, knoblist1 } => {
-                if *start_frame >= *frames || *end_frame >= *frames {
-                    return Err(format!("Tween command has frames outside range: {} to {}.", start_frame, end_frame).into());
-                }
-                
-                if start_frame > end_frame {
-                    return Err(format!("Tween command has start_frame > end_frame: {} > {}.", start_frame, end_frame).into());
-                }
+                check_frame_range("Tween", *start_frame, *end_frame, *frames)?;
 
                 let knobs0 = saved_knobs.get(knoblist0)
                     .ok_or_else(|| format!("Knoblist '{}' not found", knoblist0))?;
@@ -104,56 +185,351 @@ pub fn second_pass(commands: &Vec<Command>, frames: &usize) -> Result<Vec<HashMa
         }
     }
 
+    // a knob keeps its last value on frames that don't set it
+    // filling that in here means every frame can be rendered on its own
+    for frame in 1..frame_knobs.len() {
+        let (previous, current) = frame_knobs.split_at_mut(frame);
+
+        for (name, value) in &previous[frame - 1] {
+            current[0].entry(name.clone()).or_insert(*value);
+        }
+    }
+
     Ok(frame_knobs)
 }
 
+// every format has to be able to store the delay, apng has the smallest limit
+fn check_delay(delay_ms: f32) -> Result<(), Box<dyn Error>> {
+    if !(0.0..=MAX_ANIMATION_DELAY_MS).contains(&delay_ms) {
+        return Err(format!("Delay has to be between 0 and {} ms: {}.", MAX_ANIMATION_DELAY_MS, delay_ms).into());
+    }
+
+    Ok(())
+}
+
+// every command that works on a range of frames checks it the same way
+fn check_frame_range(command: &str, start_frame: usize, end_frame: usize, frames: usize) -> Result<(), Box<dyn Error>> {
+    if start_frame >= frames || end_frame >= frames {
+        return Err(format!("{} command has frames outside range: {} to {}.", command, start_frame, end_frame).into());
+    }
+
+    if start_frame > end_frame {
+        return Err(format!("{} command has start_frame > end_frame: {} > {}.", command, start_frame, end_frame).into());
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Square,
+}
+
+impl Waveform {
+    // one cycle per unit of x, every wave starts at 0 (except square) and stays within -1 to 1
+    pub fn sample(&self, x: f32) -> f32 {
+        match self {
+            Waveform::Sine => (2.0 * PI * x).sin(),
+            Waveform::Triangle => {
+                // shifted a quarter cycle so it rises from 0 like sine does
+                let t = x + 0.25;
+                1.0 - 4.0 * (t - t.floor() - 0.5).abs()
+            }
+            Waveform::Square => if x - x.floor() < 0.5 { 1.0 } else { -1.0 },
+        }
+    }
+}
+
+// 1d value noise: random values on every integer that get blended together with a smooth curve
+// the output is between -1 and 1 and the same seed always gives the same curve
+pub fn smooth_noise(x: f32, seed: u32) -> f32 {
+    let i = x.floor();
+    let t = x - i;
+
+    // smootherstep so the curve doesn't have any sudden changes in speed at the integers
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+
+    let a = lattice_value(i as i64, seed);
+    let b = lattice_value(i as i64 + 1, seed);
+
+    a + (b - a) * fade
+}
+
+fn lattice_value(i: i64, seed: u32) -> f32 {
+    // splitmix64 to turn the lattice point and seed into a well mixed random number
+    let mut z = (i as u64) ^ ((seed as u64) << 32);
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    (z >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+}
+
+// linearly interpolates the knob tables so a knob can be evaluated in between frames
+pub fn knobs_at(frame_knobs: &[HashMap<String, f32>], time: f32) -> HashMap<String, f32> {
+    let last = frame_knobs.len() - 1;
+    let time = time.clamp(0.0, last as f32);
+    let frame = (time.floor() as usize).min(last);
+    let next = (frame + 1).min(last);
+    let t = time - frame as f32;
+
+    let mut knobs = frame_knobs[next].clone();
+
+    for (name, start_val) in &frame_knobs[frame] {
+        let value = match frame_knobs[next].get(name) {
+            Some(end_val) => start_val + (end_val - start_val) * t,
+            None => *start_val,
+        };
+
+        knobs.insert(name.clone(), value);
+    }
+
+    knobs
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Gif,
+    Apng,
+    // every # in the pattern is replaced by a digit of the frame number
+    PngSequence { pattern: String },
+    // "-" writes the stream to stdout
+    Y4m,
+}
+
+impl OutputFormat {
+    // returns the format along with the file the animation should be written to
+    pub fn from_path(path: &str) -> Result<(Self, String), Box<dyn Error>> {
+        if path == "-" {
+            return Ok((OutputFormat::Y4m, path.to_string()));
+        }
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            // a bare basename gets the default extension
+            "" => OutputFormat::from_path(&format!("{}.{}", path, DEFAULT_ANIMATION_EXTENSION)),
+            "gif" => Ok((OutputFormat::Gif, path.to_string())),
+            "png" if path.contains('#') => Ok((OutputFormat::PngSequence { pattern: path.to_string() }, path.to_string())),
+            "png" | "apng" => Ok((OutputFormat::Apng, path.to_string())),
+            "y4m" => Ok((OutputFormat::Y4m, path.to_string())),
+            _ => Err(format!("Cannot save animation: .{} not supported.", extension).into()),
+        }
+    }
+}
+
+// replaces the run of #s in a png sequence pattern with the zero padded frame number
+// patterns without any get turned away before this (see options.rs and from_path)
+pub fn frame_file_name(pattern: &str, frame: usize) -> String {
+    let start = pattern.find('#').unwrap_or(pattern.len());
+    let width = pattern[start..].chars().take_while(|c| *c == '#').count();
+
+    format!("{}{:0width$}{}", &pattern[..start], frame, &pattern[start + width..], width = width)
+}
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(png::Writer<BufWriter<File>>),
+    PngSequence { pattern: String },
+    Y4m { output: Box<dyn Write>, frame_delay: f32 },
+}
+
 pub struct Animation {
-    pub frames: Vec<Vec<u8>>,
-    pub width: usize,
-    pub height: usize,
+    encoder: Encoder,
+    filename: String,
+    width: usize,
+    height: usize,
 }
 
 impl Animation {
-    pub fn new(width: usize, height: usize) -> Self {
-        Self {
-            frames: Vec::new(),
+    // frames are encoded as soon as they're added so we never hold more than one in memory
+    // num_frames is how many frames will be added, which can be less than the whole animation
+    pub fn new(path: &str, width: usize, height: usize, num_frames: usize, settings: &AnimationSettings) -> Result<Self, Box<dyn Error>> {
+        let (format, filename) = OutputFormat::from_path(path)?;
+
+        let encoder = match format {
+            OutputFormat::Gif => {
+                let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height)) else {
+                    return Err(format!("Gifs can't be bigger than {}x{}: {}x{}.", u16::MAX, u16::MAX, width, height).into());
+                };
+                let mut encoder = gif::Encoder::new(BufWriter::new(File::create(&filename)?), gif_width, gif_height, &[])?;
+
+                // gifs store the number of extra loops after the first play
+                let repeat = match settings.plays {
+                    0 => gif::Repeat::Infinite,
+                    plays => gif::Repeat::Finite(u16::try_from(plays - 1)?),
+                };
+                encoder.set_repeat(repeat)?;
+
+                Encoder::Gif(encoder)
+            }
+
+            OutputFormat::Apng => {
+                let mut encoder = png::Encoder::new(BufWriter::new(File::create(&filename)?), width as u32, height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(num_frames as u32, settings.plays)?;
+
+                Encoder::Apng(encoder.write_header()?)
+            }
+
+            OutputFormat::PngSequence { pattern } => {
+                if let Some(directory) = Path::new(&pattern).parent() && !directory.as_os_str().is_empty() {
+                    fs::create_dir_all(directory)?;
+                }
+
+                Encoder::PngSequence { pattern }
+            }
+
+            OutputFormat::Y4m => {
+                let mut output: Box<dyn Write> = if filename == "-" {
+                    Box::new(BufWriter::new(io::stdout()))
+                } else {
+                    Box::new(BufWriter::new(File::create(&filename)?))
+                };
+
+                // y4m has a constant frame rate so frames held longer are written multiple times
+                let frame_rate = (1_000_000.0 / settings.default_delay.max(0.001)).round() as u32;
+                writeln!(output, "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444", width, height, frame_rate)?;
+
+                Encoder::Y4m { output, frame_delay: settings.default_delay }
+            }
+        };
+
+        Ok(Self {
+            encoder,
+            filename,
             width,
             height,
-        }
+        })
     }
 
-    pub fn add_frame(&mut self, rgb_buffer: &Vec<u8>) {
-        self.frames.push(rgb_buffer.clone());
-    }
+    pub fn add_frame(&mut self, frame: usize, picture: &Picture, delay_ms: f32) -> Result<(), Box<dyn Error>> {
+        if picture.xres != self.width || picture.yres != self.height {
+            return Err(format!("Frame {} is {}x{} but the animation is {}x{}.", frame, picture.xres, picture.yres, self.width, self.height).into());
+        }
+
+        let rgb_buffer = &picture.data;
 
-    pub fn save_as_file(&self, filename: String, delay: u32) -> Result<(), Box<dyn std::error::Error>> {
-        let file = File::create(&filename)?;
-        let mut encoder = GifEncoder::new(file);
-        encoder.set_repeat(Repeat::Infinite)?;
+        match &mut self.encoder {
+            Encoder::Gif(encoder) => {
+                // quantizes down to a 256 color palette, speed 1 is the slowest and best looking
+                let mut gif_frame = gif::Frame::from_rgb_speed(self.width as u16, self.height as u16, rgb_buffer, 1);
+                gif_frame.dispose = gif::DisposalMethod::Background;
+                // gif delays are stored in hundredths of a second so anything finer gets rounded
+                gif_frame.delay = (delay_ms / 10.0).round() as u16;
 
-        for frame_data in &self.frames {
-            let mut rgba = Vec::with_capacity(self.width * self.height * 4);
+                encoder.write_frame(&gif_frame)?;
+            }
+
+            Encoder::Apng(writer) => {
+                writer.set_frame_delay(delay_ms.round().clamp(0.0, u16::MAX as f32) as u16, 1000)?;
+                writer.write_image_data(rgb_buffer)?;
+            }
 
-            // convert to rgba because the Frame struct expects rgba
-            for chunk in frame_data.chunks(3) {
-                rgba.push(chunk[0]);
-                rgba.push(chunk[1]);
-                rgba.push(chunk[2]);
-                rgba.push(255);
+            Encoder::PngSequence { pattern } => {
+                picture.save_as_file(&frame_file_name(pattern, frame))?;
+            }
+
+            Encoder::Y4m { output, frame_delay } => {
+                let repeats = (delay_ms / *frame_delay).round().max(1.0) as usize;
+                let planes = rgb_to_yuv444(rgb_buffer);
+
+                for _ in 0..repeats {
+                    writeln!(output, "FRAME")?;
+                    output.write_all(&planes)?;
+                }
             }
+        }
 
-            let img: RgbaImage = ImageBuffer::from_raw(
-                self.width as u32,
-                self.height as u32,
-                rgba,
-            ).ok_or("Failed to create RGBA frame")?;
+        Ok(())
+    }
 
-            let frame = Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(delay, 1));
-            encoder.encode_frame(frame)?;
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.encoder {
+            // writes the trailer, dropping the encoder would do it too but any error would get lost
+            Encoder::Gif(encoder) => encoder.into_inner()?.flush()?,
+            Encoder::Apng(writer) => writer.finish()?,
+            // every frame was already saved as its own file
+            Encoder::PngSequence { .. } => return Ok(()),
+            Encoder::Y4m { mut output, .. } => output.flush()?,
         }
 
-        println!("{} created.", filename);
+        // status messages all go to stderr so stdout can carry the video stream
+        if self.filename != "-" {
+            eprintln!("{} created.", self.filename);
+        }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn rgb_to_yuv444(rgb_buffer: &[u8]) -> Vec<u8> {
+    // bt.601 studio range, which is what y4m readers assume when no color range is given
+    let pixels = rgb_buffer.len() / 3;
+    let mut planes = vec![0; pixels * 3];
+
+    for (i, chunk) in rgb_buffer.chunks(3).enumerate() {
+        let r = chunk[0] as i32;
+        let g = chunk[1] as i32;
+        let b = chunk[2] as i32;
+
+        planes[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        planes[pixels + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+        planes[2 * pixels + i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+    }
+
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(knobs: &[(&str, f32)]) -> HashMap<String, f32> {
+        knobs.iter().map(|&(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn knobs_at_blends_between_frames() {
+        let frames = [frame(&[("a", 0.0), ("b", 10.0)]), frame(&[("a", 1.0), ("b", 20.0)]), frame(&[("a", 3.0), ("b", 20.0)])];
+
+        assert_eq!(knobs_at(&frames, 0.0), frames[0]);
+        assert_eq!(knobs_at(&frames, 1.0), frames[1]);
+        assert_eq!(knobs_at(&frames, 0.25), frame(&[("a", 0.25), ("b", 12.5)]));
+        assert_eq!(knobs_at(&frames, 1.5), frame(&[("a", 2.0), ("b", 20.0)]));
+    }
+
+    #[test]
+    fn knobs_at_holds_the_ends() {
+        let frames = [frame(&[("a", 0.0)]), frame(&[("a", 4.0)])];
+
+        assert_eq!(knobs_at(&frames, -1.0), frames[0]);
+        assert_eq!(knobs_at(&frames, 1.0), frames[1]);
+        assert_eq!(knobs_at(&frames, 7.5), frames[1]);
+        assert_eq!(knobs_at(&frames[..1], 0.5), frames[0]);
+    }
+
+    #[test]
+    fn frame_file_name_pads_the_frame_number_to_the_hashes() {
+        assert_eq!(frame_file_name("temp_frames/a_###.png", 7), "temp_frames/a_007.png");
+        assert_eq!(frame_file_name("temp_frames/a_###.png", 1234), "temp_frames/a_1234.png");
+        assert_eq!(frame_file_name("#.png", 3), "3.png");
+        // only the first run of #s is the frame number
+        assert_eq!(frame_file_name("a_##_#.png", 5), "a_05_#.png");
+    }
+
+    #[test]
+    fn knobs_at_keeps_knobs_only_one_frame_has() {
+        let frames = [frame(&[("a", 2.0), ("gone", 5.0)]), frame(&[("a", 4.0), ("new", 8.0)])];
+
+        assert_eq!(knobs_at(&frames, 0.5), frame(&[("a", 3.0), ("gone", 5.0), ("new", 8.0)]));
+    }
+}
@@ -16,7 +16,7 @@ impl CoordinateStack {
 
     pub fn peek(&self) -> Matrix {
         if self.data.is_empty() {
-            println!("Stack is empty, defaulting to identity matrix.");
+            eprintln!("Stack is empty, defaulting to identity matrix.");
             identity()
         } else {
             self.data.last().unwrap().to_vec()
@@ -27,7 +27,7 @@ impl CoordinateStack {
         if !self.data.is_empty() {
             self.data.pop();
         } else {
-            println!("Stack was popped when empty.");
+            eprintln!("Stack was popped when empty.");
         }
     }
 
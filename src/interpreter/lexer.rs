@@ -13,7 +13,8 @@ pub fn tokenize(path: &str, keywords: HashMap<&str, TokenType>) -> Result<VecDeq
 
     let number_regex = Regex::new(r"^-?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?$").unwrap();
     let identifier_regex = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
-    let file_path_regex = Regex::new(r"^(\.{0,2}/)?([a-zA-Z0-9_\-./]*[a-zA-Z0-9_\-])?\.([a-zA-Z0-9]+)$").unwrap();
+    // # is allowed so png sequence patterns like temp_frames/name_###.png count as paths
+    let file_path_regex = Regex::new(r"^(\.{0,2}/)?([a-zA-Z0-9_\-./#]*[a-zA-Z0-9_\-#])?\.([a-zA-Z0-9]+)$").unwrap();
 
     let lines = read_lines(path).map_err(|_| format!("Script '{}' not found", path))?;
 
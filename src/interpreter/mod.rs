@@ -14,6 +14,7 @@ use std::{
     path::Path,
 };
 
+use crate::options::Options;
 use parser::Parser;
 use run_script::evaluate_commands;
 use tokens::{TokenType, Function};
@@ -23,8 +24,24 @@ static KEYWORDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
 
     map.insert("display", TokenType::Command(Function::Display));
     map.insert("save", TokenType::Command(Function::Save));
+    map.insert("views", TokenType::Command(Function::Views));
+    map.insert("frame_all", TokenType::Command(Function::FrameAll));
+    map.insert("stereo", TokenType::Command(Function::SetStereo));
+    map.insert("depth_of_field", TokenType::Command(Function::SetDepthOfField));
+    map.insert("antialias", TokenType::Command(Function::SetAntialias));
+    map.insert("depth_func", TokenType::Command(Function::SetDepthFunc));
+    map.insert("depth_write", TokenType::Command(Function::SetDepthWrite));
+    map.insert("depth_bias", TokenType::Command(Function::SetDepthBias));
+    map.insert("linewidth", TokenType::Command(Function::SetLineWidth));
+    map.insert("line_antialias", TokenType::Command(Function::SetLineAntialias));
     map.insert("clear", TokenType::Command(Function::Clear));
     map.insert("camera", TokenType::Command(Function::SetCamera));
+    map.insert("lookat", TokenType::Command(Function::LookAt));
+    map.insert("perspective", TokenType::Command(Function::SetPerspective));
+    map.insert("ortho", TokenType::Command(Function::SetOrthographic));
+    map.insert("oblique", TokenType::Command(Function::SetOblique));
+    map.insert("screen", TokenType::Command(Function::SetScreen));
+    map.insert("viewport", TokenType::Command(Function::SetViewport));
 
     map.insert("push", TokenType::Command(Function::Push));
     map.insert("pop", TokenType::Command(Function::Pop));
@@ -58,17 +75,23 @@ static KEYWORDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     map.insert("tween", TokenType::Command(Function::Tween));
     map.insert("frames", TokenType::Command(Function::SetFrames));
     map.insert("vary", TokenType::Command(Function::VaryKnob));
+    map.insert("oscillate", TokenType::Command(Function::Oscillate));
+    map.insert("wiggle", TokenType::Command(Function::Wiggle));
     map.insert("setknobs", TokenType::Command(Function::SetAllKnobs));
+    map.insert("fps", TokenType::Command(Function::SetFps));
+    map.insert("delay", TokenType::Command(Function::SetDelay));
+    map.insert("loop", TokenType::Command(Function::SetLoop));
+    map.insert("motion_blur", TokenType::Command(Function::SetMotionBlur));
 
     map
 });
 
-pub fn run_script(path: &str) -> Result<(), Box<dyn Error>> {
-    let tokens = lexer::tokenize(path, KEYWORDS.clone())?;
+pub fn run_script(options: &Options) -> Result<(), Box<dyn Error>> {
+    let tokens = lexer::tokenize(&options.script, KEYWORDS.clone())?;
 
     let commands = Parser::new().generate_command_list(tokens)?;
 
-    evaluate_commands(commands)?;
+    evaluate_commands(commands, options)?;
 
     Ok(())
 }
@@ -5,16 +5,34 @@ use std::{
 };
 
 use crate::{
-    constants::ShadingMode,
+    constants::{DEFAULT_EDGE_BIAS, DEFAULT_EDGE_COLOR, DEFAULT_FRAME_MARGIN, DEFAULT_OBLIQUE_ANGLE},
     matrix::Rotation,
+    picture::{DepthFunc, ResampleFilter},
+    render::{Viewport, depth_of_field::DepthOfField, shader::ShadingMode, stereo::{Stereo, StereoMode}},
 };
-use super::tokens::{Token, TokenType, Function};
+use super::{
+    animation::Waveform,
+    tokens::{Token, TokenType, Function},
+};
+
+// eye, aim, up and roll for camera and lookat
+type ViewArguments = ([f32; 3], [f32; 3], [f32; 3], f32);
 
 // file paths +  identifiers stored as String
 #[derive(Clone, Debug)]
 pub enum Command {
     Display,
-    Save { file_path: String },
+    Save { file_path: String, camera: Option<String> },
+    Views { cameras: Vec<String> },
+    FrameAll { margin: f32 },
+    SetStereo { stereo: Option<Stereo> },
+    SetDepthOfField { depth_of_field: Option<DepthOfField> },
+    SetAntialias { factor: usize, filter: ResampleFilter },
+    SetDepthFunc { func: DepthFunc },
+    SetDepthWrite { write: bool },
+    SetDepthBias { bias: f32, slope_bias: f32 },
+    SetLineWidth { width: f32 },
+    SetLineAntialias { antialias: bool },
     Clear,
     Push,
     Pop,
@@ -34,14 +52,26 @@ pub enum Command {
     SetAmbient { r: f32, g: f32, b: f32 },
     SetConstants { name: String, kar: f32, kdr: f32, ksr: f32, kag: f32, kdg: f32, ksg: f32, kab: f32, kdb: f32, ksb: f32 },
     SetShading { shading_mode: ShadingMode },
-    SetCamera { eye_x: f32, eye_y: f32, eye_z: f32, aim_x: f32, aim_y: f32, aim_z: f32 },
+    SetCamera { name: Option<String>, eye: [f32; 3], aim: [f32; 3], up: [f32; 3], roll: f32 },
+    LookAt { eye: [f32; 3], aim: [f32; 3], up: [f32; 3], roll: f32 },
+    SetPerspective { fov: f32, near: f32, far: f32 },
+    SetOrthographic { extent: Option<Viewport> },
+    SetOblique { angle: f32, depth_scale: f32, extent: Option<Viewport> },
+    SetScreen { width: usize, height: usize },
+    SetViewport { xmin: f32, ymin: f32, xmax: f32, ymax: f32 },
     SetBaseName { name: String },
     SetKnob { name: String, value: f32 },
     SaveKnobList { name: String },
     Tween { start_frame: usize, end_frame: usize, knoblist0: String, knoblist1: String },
     SetFrames { num_frames: usize },
     VaryKnob { knob: String, start_frame: usize, end_frame: usize, start_val: f32, end_val: f32 },
+    Oscillate { knob: String, start_frame: usize, end_frame: usize, period: f32, amplitude: f32, phase: f32, waveform: Waveform },
+    Wiggle { knob: String, start_frame: usize, end_frame: usize, frequency: f32, amplitude: f32, seed: u32 },
     SetAllKnobs { value: f32 },
+    SetFps { fps: f32 },
+    SetDelay { delay_ms: f32, start_frame: Option<usize>, end_frame: Option<usize> },
+    SetLoop { plays: u32 },
+    SetMotionBlur { samples: usize, shutter: f32 },
 }
 
 pub struct Parser {
@@ -61,7 +91,26 @@ impl Parser {
 
         None
     }
+
+    fn pop_optional_number(&mut self) -> Option<String> {
+        if let Some(token) = self.stack.front() && token.token_type == TokenType::Number {
+            let token = self.stack.pop_front().unwrap();
+            return Some(token.value.clone())
+        }
+
+        None
+    }
     
+    // pops numbers until there aren't any more or there's max of them
+    fn pop_optional_numbers(&mut self, max: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+        let mut numbers = vec![];
+        while numbers.len() < max && let Some(number) = self.pop_optional_number() {
+            numbers.push(Parser::convert_to_f32(number)?);
+        }
+
+        Ok(numbers)
+    }
+
     fn pop(&mut self) -> Result<Token, Box<dyn Error>> {
         if let Some(token) = self.stack.pop_front() {
             Ok(token)
@@ -84,6 +133,16 @@ impl Parser {
                         match function {
                             Function::Display => { Command::Display }
                             Function::Save => { self.handle_save()? }
+                            Function::Views => { self.handle_views()? }
+                            Function::FrameAll => { self.handle_frame_all()? }
+                            Function::SetStereo => { self.handle_set_stereo()? }
+                            Function::SetDepthOfField => { self.handle_set_depth_of_field()? }
+                            Function::SetAntialias => { self.handle_set_antialias()? }
+                            Function::SetDepthFunc => { self.handle_set_depth_func()? }
+                            Function::SetDepthWrite => { self.handle_set_depth_write()? }
+                            Function::SetDepthBias => { self.handle_set_depth_bias()? }
+                            Function::SetLineWidth => { self.handle_set_line_width()? }
+                            Function::SetLineAntialias => { self.handle_set_line_antialias()? }
                             Function::Clear => { Command::Clear }
                             Function::Push => { Command::Push }
                             Function::Pop => { Command::Pop }
@@ -104,13 +163,25 @@ impl Parser {
                             Function::SetConstants => { self.handle_set_constants()? }
                             Function::SetShading => { self.handle_set_shading()? }
                             Function::SetCamera => { self.handle_set_camera()? }
+                            Function::LookAt => { self.handle_look_at()? }
+                            Function::SetPerspective => { self.handle_set_perspective()? }
+                            Function::SetOrthographic => { self.handle_set_orthographic()? }
+                            Function::SetOblique => { self.handle_set_oblique()? }
+                            Function::SetScreen => { self.handle_set_screen()? }
+                            Function::SetViewport => { self.handle_set_viewport()? }
                             Function::SetBaseName => { self.handle_set_base_name()? }
                             Function::SetKnob => { self.handle_set_knob()? }
                             Function::SaveKnobList => { self.handle_save_knob_list()? }
                             Function::Tween => { self.handle_tween()? }
                             Function::SetFrames => { self.handle_set_frames()? }
                             Function::VaryKnob => { self.handle_vary_knob()? }
+                            Function::Oscillate => { self.handle_oscillate()? }
+                            Function::Wiggle => { self.handle_wiggle()? }
                             Function::SetAllKnobs => { self.handle_set_all_knobs()? }
+                            Function::SetFps => { self.handle_set_fps()? }
+                            Function::SetDelay => { self.handle_set_delay()? }
+                            Function::SetLoop => { self.handle_set_loop()? }
+                            Function::SetMotionBlur => { self.handle_set_motion_blur()? }
                         }
                     )
                 }
@@ -126,8 +197,154 @@ impl Parser {
 
     fn handle_save(&mut self) -> Result<Command, Box<dyn Error>> {
         let file_path = self.pop()?.value;
+        let camera = self.pop_optional_identifier();
+
+        Ok(Command::Save { file_path, camera })
+    }
+
+    fn handle_views(&mut self) -> Result<Command, Box<dyn Error>> {
+        let mut cameras = vec![];
+        while let Some(camera) = self.pop_optional_identifier() {
+            cameras.push(camera);
+        }
+
+        if cameras.is_empty() {
+            return Err("Expected at least one camera name after views.".into());
+        }
+
+        Ok(Command::Views { cameras })
+    }
+
+    fn handle_frame_all(&mut self) -> Result<Command, Box<dyn Error>> {
+        let margin = match self.pop_optional_number() {
+            Some(margin) => Parser::convert_to_f32(margin)?,
+            None => DEFAULT_FRAME_MARGIN,
+        };
+
+        if !(0.0..0.5).contains(&margin) {
+            return Err(format!("Frame margin has to be at least 0 and less than 0.5: {}", margin).into());
+        }
+
+        Ok(Command::FrameAll { margin })
+    }
+
+    // for settings that can be turned off again with "off" instead of their arguments
+    fn pop_off(&mut self) -> bool {
+        if let Some(token) = self.stack.front() && token.token_type == TokenType::Identifier && token.value == "off" {
+            self.stack.pop_front();
+            return true;
+        }
+
+        false
+    }
+
+    fn handle_set_stereo(&mut self) -> Result<Command, Box<dyn Error>> {
+        if self.pop_off() {
+            return Ok(Command::SetStereo { stereo: None });
+        }
+
+        let eye_separation = Parser::convert_to_f32(self.pop()?.value)?;
+        let convergence = Parser::convert_to_f32(self.pop()?.value)?;
+
+        if convergence <= 0.0 {
+            return Err(format!("Stereo convergence distance has to be positive: {}", convergence).into());
+        }
+
+        let mode = match self.pop_optional_identifier().as_deref() {
+            None | Some("anaglyph") => StereoMode::Anaglyph,
+            Some("side_by_side") => StereoMode::SideBySide,
+            Some(mode) => return Err(format!("Invalid stereo mode: {}", mode).into()),
+        };
 
-        Ok(Command::Save { file_path })
+        Ok(Command::SetStereo { stereo: Some(Stereo { eye_separation, convergence, mode }) })
+    }
+
+    fn handle_set_depth_of_field(&mut self) -> Result<Command, Box<dyn Error>> {
+        if self.pop_off() {
+            return Ok(Command::SetDepthOfField { depth_of_field: None });
+        }
+
+        let aperture = Parser::convert_to_f32(self.pop()?.value)?;
+        let focus_distance = Parser::convert_to_f32(self.pop()?.value)?;
+        let samples = Parser::convert_to_usize(self.pop()?.value)?;
+
+        if aperture < 0.0 || focus_distance <= 0.0 || samples == 0 {
+            return Err(format!(
+                "Depth of field needs a non-negative aperture, a positive focus distance and at least one sample: {} {} {}",
+                aperture, focus_distance, samples,
+            ).into());
+        }
+
+        Ok(Command::SetDepthOfField { depth_of_field: Some(DepthOfField { aperture, focus_distance, samples }) })
+    }
+
+    fn handle_set_antialias(&mut self) -> Result<Command, Box<dyn Error>> {
+        let factor = Parser::convert_to_usize(self.pop()?.value)?;
+        if factor == 0 {
+            return Err("Antialiasing needs a factor of at least 1.".into());
+        }
+
+        let filter = match self.pop_optional_identifier().as_deref() {
+            None | Some("box") => ResampleFilter::Box,
+            Some("tent") => ResampleFilter::Tent,
+            Some(filter) => return Err(format!("Unknown antialiasing filter, expected box or tent: {}", filter).into()),
+        };
+
+        Ok(Command::SetAntialias { factor, filter })
+    }
+
+    fn handle_set_depth_func(&mut self) -> Result<Command, Box<dyn Error>> {
+        let func_str = self.pop()?.value.to_lowercase();
+        let func = match func_str.as_str() {
+            "less" => DepthFunc::Less,
+            "less_equal" => DepthFunc::LessEqual,
+            "greater" => DepthFunc::Greater,
+            "always" => DepthFunc::Always,
+            _ => return Err(format!("Invalid depth function, expected less, less_equal, greater or always: {}", func_str).into()),
+        };
+
+        Ok(Command::SetDepthFunc { func })
+    }
+
+    fn pop_on_off(&mut self, command: &str) -> Result<bool, Box<dyn Error>> {
+        let value = self.pop()?.value.to_lowercase();
+        match value.as_str() {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(format!("Expected on or off for {}: {}", command, value).into()),
+        }
+    }
+
+    fn handle_set_depth_write(&mut self) -> Result<Command, Box<dyn Error>> {
+        let write = self.pop_on_off("depth_write")?;
+
+        Ok(Command::SetDepthWrite { write })
+    }
+
+    // depth_bias bias [slope_bias]
+    fn handle_set_depth_bias(&mut self) -> Result<Command, Box<dyn Error>> {
+        let bias = Parser::convert_to_f32(self.pop()?.value)?;
+        let slope_bias = match self.pop_optional_number() {
+            Some(slope_bias) => Parser::convert_to_f32(slope_bias)?,
+            None => 0.0,
+        };
+
+        Ok(Command::SetDepthBias { bias, slope_bias })
+    }
+
+    fn handle_set_line_width(&mut self) -> Result<Command, Box<dyn Error>> {
+        let width = Parser::convert_to_f32(self.pop()?.value)?;
+        if width <= 0.0 {
+            return Err(format!("Line width has to be positive: {}", width).into());
+        }
+
+        Ok(Command::SetLineWidth { width })
+    }
+
+    fn handle_set_line_antialias(&mut self) -> Result<Command, Box<dyn Error>> {
+        let antialias = self.pop_on_off("line_antialias")?;
+
+        Ok(Command::SetLineAntialias { antialias })
     }
 
     fn handle_move(&mut self) -> Result<Command, Box<dyn Error>> {
@@ -298,26 +515,156 @@ impl Parser {
 
     fn handle_set_shading(&mut self) -> Result<Command, Box<dyn Error>> {
         let mode_str = self.pop()?.value.to_lowercase();
-        let shading_mode = match mode_str.as_str() {
-            "wireframe" => ShadingMode::Wireframe,
-            "flat" => ShadingMode::Flat,
-            "gouraud" => ShadingMode::Gouraud,
-            "phong" => ShadingMode::Phong,
-            _ => return Err(format!("Invalid shading mode: {}", mode_str).into()),
-        };
+        // wireframe, hidden_line or the name of a shader, see render/shader.rs
+        let mut shading_mode = ShadingMode::named(&mode_str).ok_or(format!("Invalid shading mode: {}", mode_str))?;
+
+        // edges [r g b [bias]] draws the edges on top
+        if let Some(token) = self.stack.front() && token.token_type == TokenType::Identifier && token.value == "edges" {
+            self.pop()?;
+            let numbers = self.pop_optional_numbers(4)?;
+
+            let (mut color, mut bias) = (DEFAULT_EDGE_COLOR, DEFAULT_EDGE_BIAS);
+            let shader = match shading_mode {
+                ShadingMode::Wireframe => return Err("Wireframe is already only edges.".into()),
+                ShadingMode::Shader(shader) => shader,
+                ShadingMode::ShadedEdges { shader, color: old_color, bias: old_bias } => {
+                    (color, bias) = (old_color, old_bias);
+                    shader
+                }
+            };
+
+            match numbers.as_slice() {
+                [] => {}
+                [r, g, b] => color = (*r as usize, *g as usize, *b as usize),
+                [r, g, b, edge_bias] => {
+                    color = (*r as usize, *g as usize, *b as usize);
+                    bias = *edge_bias;
+                }
+                _ => return Err(format!("Expected an edge color and maybe a bias after edges, got {} numbers.", numbers.len()).into()),
+            }
+
+            shading_mode = ShadingMode::ShadedEdges { shader, color, bias };
+        }
 
         Ok(Command::SetShading { shading_mode })
     }
 
     fn handle_set_camera(&mut self) -> Result<Command, Box<dyn Error>> {
-        let eye_x = Parser::convert_to_f32(self.pop()?.value)?;
-        let eye_y = Parser::convert_to_f32(self.pop()?.value)?;
-        let eye_z = Parser::convert_to_f32(self.pop()?.value)?;
-        let aim_x = Parser::convert_to_f32(self.pop()?.value)?;
-        let aim_y = Parser::convert_to_f32(self.pop()?.value)?;
-        let aim_z = Parser::convert_to_f32(self.pop()?.value)?;
+        let name = self.pop_optional_identifier();
+        let (eye, aim, up, roll) = self.handle_view_arguments()?;
+
+        Ok(Command::SetCamera { name, eye, aim, up, roll })
+    }
+
+    fn handle_look_at(&mut self) -> Result<Command, Box<dyn Error>> {
+        let (eye, aim, up, roll) = self.handle_view_arguments()?;
+
+        Ok(Command::LookAt { eye, aim, up, roll })
+    }
+
+    fn handle_view_arguments(&mut self) -> Result<ViewArguments, Box<dyn Error>> {
+        let mut numbers = vec![];
+        for _ in 0..6 {
+            numbers.push(Parser::convert_to_f32(self.pop()?.value)?);
+        }
+
+        let eye = [numbers[0], numbers[1], numbers[2]];
+        let aim = [numbers[3], numbers[4], numbers[5]];
+
+        if eye == aim {
+            return Err(format!("Camera can't aim at the point it's at: {} {} {}", eye[0], eye[1], eye[2]).into());
+        }
+
+        let optional = self.pop_optional_numbers(4)?;
+
+        match optional.len() {
+            0 => Ok((eye, aim, [0.0, 1.0, 0.0], 0.0)),
+            1 => Ok((eye, aim, [0.0, 1.0, 0.0], optional[0])),
+            3 => Ok((eye, aim, [optional[0], optional[1], optional[2]], 0.0)),
+            4 => Ok((eye, aim, [optional[0], optional[1], optional[2]], optional[3])),
+            _ => Err("Expected an up vector (3 numbers), a roll (1 number) or both after the camera's eye and aim.".into()),
+        }
+    }
+
+    fn handle_set_perspective(&mut self) -> Result<Command, Box<dyn Error>> {
+        let fov = Parser::convert_to_f32(self.pop()?.value)?;
+        let near = Parser::convert_to_f32(self.pop()?.value)?;
+        let far = Parser::convert_to_f32(self.pop()?.value)?;
+
+        if fov <= 0.0 || fov >= 180.0 {
+            return Err(format!("Field of view has to be between 0 and 180 degrees: {}", fov).into());
+        }
+
+        if near <= 0.0 || far <= near {
+            return Err(format!("Perspective needs 0 < near < far: {} {}", near, far).into());
+        }
+
+        Ok(Command::SetPerspective { fov, near, far })
+    }
+
+    fn handle_set_screen(&mut self) -> Result<Command, Box<dyn Error>> {
+        let width = Parser::convert_to_usize(self.pop()?.value)?;
+        let height = Parser::convert_to_usize(self.pop()?.value)?;
+
+        if width == 0 || height == 0 {
+            return Err(format!("Screen size has to be at least 1x1: {}x{}", width, height).into());
+        }
+
+        Ok(Command::SetScreen { width, height })
+    }
+
+    fn handle_set_viewport(&mut self) -> Result<Command, Box<dyn Error>> {
+        let xmin = Parser::convert_to_f32(self.pop()?.value)?;
+        let ymin = Parser::convert_to_f32(self.pop()?.value)?;
+        let xmax = Parser::convert_to_f32(self.pop()?.value)?;
+        let ymax = Parser::convert_to_f32(self.pop()?.value)?;
+
+        Parser::check_extent(xmin, ymin, xmax, ymax)?;
+
+        Ok(Command::SetViewport { xmin, ymin, xmax, ymax })
+    }
+
+    fn check_extent(xmin: f32, ymin: f32, xmax: f32, ymax: f32) -> Result<Viewport, Box<dyn Error>> {
+        if xmax <= xmin || ymax <= ymin {
+            return Err(format!("Viewport has to have a positive width and height: {} {} {} {}", xmin, ymin, xmax, ymax).into());
+        }
+
+        Ok(Viewport { xmin, ymin, xmax, ymax })
+    }
 
-        Ok(Command::SetCamera { eye_x, eye_y, eye_z, aim_x, aim_y, aim_z })
+    // ortho [xmin ymin xmax ymax]
+    fn handle_set_orthographic(&mut self) -> Result<Command, Box<dyn Error>> {
+        let numbers = self.pop_optional_numbers(4)?;
+
+        let extent = match numbers.len() {
+            0 => None,
+            4 => Some(Parser::check_extent(numbers[0], numbers[1], numbers[2], numbers[3])?),
+            _ => return Err("Expected nothing or an extent (xmin ymin xmax ymax) after ortho.".into()),
+        };
+
+        Ok(Command::SetOrthographic { extent })
+    }
+
+    // oblique cabinet|cavalier [angle] [xmin ymin xmax ymax]
+    fn handle_set_oblique(&mut self) -> Result<Command, Box<dyn Error>> {
+        let kind = self.pop()?.value;
+        let depth_scale = match kind.as_str() {
+            "cabinet" => 0.5,
+            "cavalier" => 1.0,
+            _ => return Err(format!("Invalid oblique projection, expected cabinet or cavalier: {}", kind).into()),
+        };
+
+        let numbers = self.pop_optional_numbers(5)?;
+
+        let (angle, extent) = match numbers.len() {
+            0 => (DEFAULT_OBLIQUE_ANGLE, None),
+            1 => (numbers[0], None),
+            4 => (DEFAULT_OBLIQUE_ANGLE, Some(Parser::check_extent(numbers[0], numbers[1], numbers[2], numbers[3])?)),
+            5 => (numbers[0], Some(Parser::check_extent(numbers[1], numbers[2], numbers[3], numbers[4])?)),
+            _ => return Err("Expected an angle, an extent (xmin ymin xmax ymax) or both after oblique.".into()),
+        };
+
+        Ok(Command::SetOblique { angle, depth_scale, extent })
     }
 
     fn handle_set_base_name(&mut self) -> Result<Command, Box<dyn Error>> {
@@ -364,12 +711,73 @@ impl Parser {
         Ok(Command::VaryKnob { knob, start_frame, end_frame, start_val, end_val })
     }
 
+    fn handle_oscillate(&mut self) -> Result<Command, Box<dyn Error>> {
+        let knob = self.pop()?.value;
+        let start_frame = Parser::convert_to_usize(self.pop()?.value)?;
+        let end_frame = Parser::convert_to_usize(self.pop()?.value)?;
+        let period = Parser::convert_to_f32(self.pop()?.value)?;
+        let amplitude = Parser::convert_to_f32(self.pop()?.value)?;
+        let phase = Parser::convert_to_f32(self.pop()?.value)?;
+
+        let waveform = match self.pop_optional_identifier().map(|name| name.to_lowercase()).as_deref() {
+            None | Some("sine") => Waveform::Sine,
+            Some("triangle") => Waveform::Triangle,
+            Some("square") => Waveform::Square,
+            Some(name) => return Err(format!("Invalid waveform: {}", name).into()),
+        };
+
+        Ok(Command::Oscillate { knob, start_frame, end_frame, period, amplitude, phase, waveform })
+    }
+
+    fn handle_wiggle(&mut self) -> Result<Command, Box<dyn Error>> {
+        let knob = self.pop()?.value;
+        let start_frame = Parser::convert_to_usize(self.pop()?.value)?;
+        let end_frame = Parser::convert_to_usize(self.pop()?.value)?;
+        let frequency = Parser::convert_to_f32(self.pop()?.value)?;
+        let amplitude = Parser::convert_to_f32(self.pop()?.value)?;
+        let seed = Parser::convert_to_usize(self.pop()?.value)? as u32;
+
+        Ok(Command::Wiggle { knob, start_frame, end_frame, frequency, amplitude, seed })
+    }
+
     fn handle_set_all_knobs(&mut self) -> Result<Command, Box<dyn Error>> {
         let value = Parser::convert_to_f32(self.pop()?.value)?;
 
         Ok(Command::SetAllKnobs { value })
     }
 
+    fn handle_set_fps(&mut self) -> Result<Command, Box<dyn Error>> {
+        let fps = Parser::convert_to_f32(self.pop()?.value)?;
+
+        Ok(Command::SetFps { fps })
+    }
+
+    fn handle_set_delay(&mut self) -> Result<Command, Box<dyn Error>> {
+        let delay_ms = Parser::convert_to_f32(self.pop()?.value)?;
+
+        // an optional frame range only holds those frames for delay_ms
+        let (start_frame, end_frame) = match self.pop_optional_number() {
+            Some(start) => (Some(Parser::convert_to_usize(start)?), Some(Parser::convert_to_usize(self.pop()?.value)?)),
+            None => (None, None),
+        };
+
+        Ok(Command::SetDelay { delay_ms, start_frame, end_frame })
+    }
+
+    fn handle_set_loop(&mut self) -> Result<Command, Box<dyn Error>> {
+        let value = self.pop()?.value;
+        let plays = value.parse::<u32>().map_err(|_| format!("Loop count has to be a whole number of at least 0: {}", value))?;
+
+        Ok(Command::SetLoop { plays })
+    }
+
+    fn handle_set_motion_blur(&mut self) -> Result<Command, Box<dyn Error>> {
+        let samples = Parser::convert_to_usize(self.pop()?.value)?;
+        let shutter = Parser::convert_to_f32(self.pop()?.value)?;
+
+        Ok(Command::SetMotionBlur { samples, shutter })
+    }
+
 
     fn convert_to_f32(parameter: String) -> Result<f32, Box<dyn Error>> {
         Ok(parameter.parse().expect(format!("Error parsing float: {}", parameter).as_str()))
@@ -1,21 +1,35 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::HashMap, error::Error, fs::OpenOptions, path::Path, vec
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs::OpenOptions,
+    path::Path,
+    sync::{Condvar, Mutex, mpsc, atomic::{AtomicBool, AtomicUsize, Ordering}},
+    thread,
+    vec,
 };
 
 use stl_io::read_stl;
 
 use crate::{
     constants::{
-        DEFAULT_ANIMATION_DELAY_MS, DEFAULT_BACKGROUND_COLOR, DEFAULT_FOREGROUND_COLOR, DEFAULT_LIGHTING_CONFIG, DEFAULT_PICTURE_DIMENSIONS, DEFAULT_REFLECTION_CONSTANTS, DEFAULT_SHADING_MODE, GENERATE_TEMPORARY_FRAME_FILES, ShadingMode
-    }, interpreter::animation::Animation, matrix, render::{
+        DEFAULT_BACKGROUND_COLOR, DEFAULT_FOREGROUND_COLOR, DEFAULT_FRAME_MARGIN, DEFAULT_LIGHTING_CONFIG, DEFAULT_PICTURE_DIMENSIONS, DEFAULT_PROJECTION, DEFAULT_REFLECTION_CONSTANTS, DEFAULT_SHADING, DEFAULT_VIEWPORT
+    }, interpreter::animation::{Animation, AnimationSettings, OutputFormat}, matrix, picture::{Accumulator, DepthTest, LineStyle, ResampleFilter}, options::Options, render::{
         LightingConfig,
         Picture,
+        Projection,
         ReflectionConstants,
+        Viewport,
+        clipping::clip_line,
+        depth_of_field::DepthOfField,
         edge_list::{add_bezier_curve, add_circle, add_edge, add_hermite_curve, render_edges},
-        polygon_list::{add_box, add_polygon, add_sphere, add_torus, render_polygons},
-    }, vector::{cross_product, dot_product, normalize_vector, subtract_vectors}
+        framing,
+        geometry_cache,
+        polygon_list::{add_box, add_polygon, render_polygons, set_frame_jobs},
+        shader::ShadingMode,
+        stereo::Stereo,
+    }
 };
 use super::{
     coordinate_stack::CoordinateStack,
@@ -26,9 +40,25 @@ use super::{
 
 type Matrix = Vec<[f32; 4]>;
 
+#[derive(Clone)]
 enum Symbol {
     Constants(ReflectionConstants),
     Knob(f32),
+    Camera(Matrix),
+}
+
+// everything drawn so far in world space, so the scene can be drawn again from a named camera
+enum SceneItem {
+    Edges(Matrix),
+    Polygons {
+        polygons: Matrix,
+        shading_mode: ShadingMode,
+        lighting_config: LightingConfig,
+        reflection_constants: ReflectionConstants,
+    },
+    // depth settings change in between draws, so they're part of the scene too
+    DepthTest(DepthTest),
+    LineStyle(LineStyle),
 }
 
 struct ScriptContext {
@@ -39,8 +69,19 @@ struct ScriptContext {
     shading_mode: ShadingMode,
     lighting_config: LightingConfig,
     reflection_constants: ReflectionConstants,
-    camera_matrix: Matrix,
+    // None until the script sets a camera, the projection picks a default one
+    camera_matrix: Option<Matrix>,
+    projection: Projection,
+    // until the script sets a viewport, it follows the shape of the screen so nothing gets stretched
+    viewport_set: bool,
     symbols: HashMap<String, Symbol>,
+    scene: Vec<SceneItem>,
+    // margin and the first scene item after frame_all, those wait to be drawn until the scene gets saved
+    framing: Option<(f32, usize)>,
+    stereo: Option<Stereo>,
+    depth_of_field: Option<DepthOfField>,
+    // how many times bigger the scene gets drawn in each direction before it's filtered back down
+    antialias: (usize, ResampleFilter),
 }
 
 impl ScriptContext {
@@ -50,25 +91,64 @@ impl ScriptContext {
             edges: matrix::new(),
             polygons: matrix::new(),
             coordinate_stack: CoordinateStack::new(),
-            shading_mode: DEFAULT_SHADING_MODE,
+            shading_mode: ShadingMode::named(DEFAULT_SHADING).unwrap(),
             lighting_config: DEFAULT_LIGHTING_CONFIG,
             reflection_constants: DEFAULT_REFLECTION_CONSTANTS,
-            camera_matrix: matrix::identity(),
+            camera_matrix: None,
+            projection: DEFAULT_PROJECTION,
+            viewport_set: false,
             symbols: HashMap::new(),
+            scene: vec![],
+            framing: None,
+            stereo: None,
+            depth_of_field: None,
+            antialias: (1, ResampleFilter::Box),
         }
     }
 
-    fn frame_reset(&mut self) {
-        self.picture = Picture::new(DEFAULT_PICTURE_DIMENSIONS.0, DEFAULT_PICTURE_DIMENSIONS.1, 255, &DEFAULT_BACKGROUND_COLOR);
-        self.edges = matrix::new();
-        self.polygons = matrix::new();
-        self.coordinate_stack = CoordinateStack::new();
+    // the constants and named cameras the whole script defines, which every animation frame starts with
+    fn symbol_table(commands: &[Command]) -> Result<HashMap<String, Symbol>, Box<dyn Error>> {
+        let mut context = ScriptContext::new();
+
+        for command in commands {
+            if matches!(command, Command::SetConstants { .. } | Command::SetCamera { name: Some(_), .. }) {
+                execute_command(command, &mut context)?;
+            }
+        }
+
+        Ok(context.symbols)
+    }
+
+    // a context for rendering one animation frame, the same no matter which thread it's on or what frame came before it
+    // only the symbol table carries over, every setting starts from its default and changes when the frame gets to it
+    // just like in a static render
+    fn for_frame(symbols: &HashMap<String, Symbol>, knobs: &HashMap<String, f32>) -> Self {
+        let mut context = ScriptContext::new();
+        context.symbols = symbols.clone();
+
+        for (name, value) in knobs {
+            context.set_knob(name.clone(), *value);
+        }
+
+        context
+    }
+
+    fn view_matrix(&self) -> Matrix {
+        match &self.camera_matrix {
+            Some(camera_matrix) => camera_matrix.clone(),
+            None => self.projection.default_camera(&self.picture.viewport),
+        }
     }
 
     fn render_edges(&mut self) {
-        matrix::multiply(&self.coordinate_stack.peek(), &mut self.edges);
-        render_edges(&self.edges, &mut self.picture, &DEFAULT_FOREGROUND_COLOR);
-        self.edges = matrix::new();
+        let mut edges = std::mem::take(&mut self.edges);
+        matrix::multiply(&self.coordinate_stack.peek(), &mut edges);
+
+        let item = SceneItem::Edges(edges);
+        if self.framing.is_none() {
+            draw_item(&item, &self.view_matrix(), &self.projection, &mut self.picture);
+        }
+        self.scene.push(item);
     }
 
     fn render_polygons(&mut self, constants: &Option<String>) -> Result<(), Box<dyn Error>> {
@@ -81,15 +161,155 @@ impl ScriptContext {
             }
         }
 
-        matrix::multiply(&self.coordinate_stack.peek(), &mut self.polygons);
-        matrix::multiply(&self.camera_matrix, &mut self.polygons);
+        let mut polygons = std::mem::take(&mut self.polygons);
+        matrix::multiply(&self.coordinate_stack.peek(), &mut polygons);
 
-        render_polygons(&self.polygons, &mut self.picture, &DEFAULT_FOREGROUND_COLOR, &self.shading_mode, &self.lighting_config, reflection_constants);
-        self.polygons = matrix::new();
+        let item = SceneItem::Polygons {
+            polygons,
+            shading_mode: self.shading_mode.clone(),
+            lighting_config: self.lighting_config,
+            reflection_constants: *reflection_constants,
+        };
+        if self.framing.is_none() {
+            draw_item(&item, &self.view_matrix(), &self.projection, &mut self.picture);
+        }
+        self.scene.push(item);
 
         Ok(())
     }
 
+    // forgets everything drawn so far, the depth and line settings still hold for whatever gets drawn next
+    fn clear_scene(&mut self) {
+        self.scene.clear();
+        self.framing = None;
+        self.scene.push(SceneItem::DepthTest(self.picture.depth_test));
+        self.scene.push(SceneItem::LineStyle(self.picture.line_style));
+    }
+
+    fn set_depth_test(&mut self, depth_test: DepthTest) {
+        self.picture.depth_test = depth_test;
+        self.scene.push(SceneItem::DepthTest(depth_test));
+    }
+
+    fn set_line_style(&mut self, line_style: LineStyle) {
+        self.picture.line_style = line_style;
+        self.scene.push(SceneItem::LineStyle(line_style));
+    }
+
+    // scales and moves everything since frame_all to fit the picture as seen from the current camera, then draws it
+    fn finish_framing(&mut self) {
+        let Some((margin, start)) = self.framing.take() else {
+            return;
+        };
+
+        let view_matrix = self.view_matrix();
+        let mut points = matrix::new();
+        for item in &self.scene[start..] {
+            match item {
+                SceneItem::Edges(edges) => points.extend_from_slice(edges),
+                SceneItem::Polygons { polygons, .. } => points.extend_from_slice(polygons),
+                SceneItem::DepthTest(_) | SceneItem::LineStyle(_) => {}
+            }
+        }
+        matrix::multiply(&view_matrix, &mut points);
+
+        if let Some(bounds) = framing::bounds(points.iter()) {
+            // the fit is in view space, so go into view space, fit, then come back out
+            let mut transform = view_matrix.clone();
+            matrix::multiply(&framing::fit(&bounds, &self.projection, &self.picture.viewport, margin), &mut transform);
+            matrix::multiply(&matrix::invert_rigid(&view_matrix), &mut transform);
+
+            for item in &mut self.scene[start..] {
+                match item {
+                    SceneItem::Edges(edges) => matrix::multiply(&transform, edges),
+                    SceneItem::Polygons { polygons, .. } => matrix::multiply(&transform, polygons),
+                    SceneItem::DepthTest(_) | SceneItem::LineStyle(_) => {}
+                }
+            }
+        }
+
+        // the depth and line settings get changed by the recorded items as they're drawn, then put back to what they are now
+        let (depth_test, line_style) = (self.picture.depth_test, self.picture.line_style);
+        for item in &self.scene[start..] {
+            draw_item(item, &view_matrix, &self.projection, &mut self.picture);
+        }
+        (self.picture.depth_test, self.picture.line_style) = (depth_test, line_style);
+    }
+
+    fn render_scene(&self, view_matrix: &Matrix, viewport: &Viewport, xres: usize, yres: usize) -> Picture {
+        let (factor, filter) = self.antialias;
+
+        // the viewport stays the same so the bigger picture shows exactly the same part of the world
+        let mut picture = Picture::new(xres * factor, yres * factor, 255, &DEFAULT_BACKGROUND_COLOR);
+        picture.viewport = *viewport;
+        // and lines stay as wide as they'd be without it
+        picture.line_scale = factor as f32;
+
+        for item in &self.scene {
+            draw_item(item, view_matrix, &self.projection, &mut picture);
+        }
+
+        if factor > 1 {
+            picture.downsample(factor, filter)
+        } else {
+            picture
+        }
+    }
+
+    // the scene from one view, averaged over the lens if depth of field is on
+    fn render_view(&self, view_matrix: &Matrix, viewport: &Viewport, xres: usize, yres: usize) -> Result<Picture, Box<dyn Error>> {
+        let Some(depth_of_field) = self.depth_of_field else {
+            return Ok(self.render_scene(view_matrix, viewport, xres, yres));
+        };
+
+        // the lens needs rays that spread out from the eye, orthographic and oblique would only get shifted around
+        if !matches!(self.projection, Projection::Perspective { .. }) {
+            return Err("Depth of field only works with a perspective projection.".into());
+        }
+
+        let mut accumulator = Accumulator::new(xres, yres);
+        for sample in 0..depth_of_field.samples {
+            accumulator.add(&self.render_scene(&depth_of_field.sample_view(view_matrix, sample), viewport, xres, yres));
+        }
+
+        let mut picture = accumulator.to_picture();
+        picture.viewport = *viewport;
+        Ok(picture)
+    }
+
+    fn camera(&self, name: &str) -> Result<&Matrix, Box<dyn Error>> {
+        match self.symbols.get(name) {
+            Some(Symbol::Camera(view_matrix)) => Ok(view_matrix),
+            _ => Err(format!("Expected symbol to be a camera: {}", name).into()),
+        }
+    }
+
+    // draws everything so far again from a named camera, in stereo, with depth of field and/or antialiased
+    // None means the picture that's already been drawn is the right one
+    fn render_output(&mut self, camera: &Option<String>) -> Result<Option<Picture>, Box<dyn Error>> {
+        self.finish_framing();
+
+        let view_matrix = match camera {
+            Some(name) => self.camera(name)?.clone(),
+            None => self.view_matrix(),
+        };
+        let (xres, yres) = (self.picture.xres, self.picture.yres);
+        let viewport = self.picture.viewport;
+
+        if let Some(stereo) = self.stereo {
+            let left = self.render_view(&stereo.eye_view(&view_matrix, &self.projection, true), &viewport, xres, yres)?;
+            let right = self.render_view(&stereo.eye_view(&view_matrix, &self.projection, false), &viewport, xres, yres)?;
+
+            return Ok(Some(stereo.combine(&left, &right)));
+        }
+
+        if camera.is_some() || self.depth_of_field.is_some() || self.antialias.0 > 1 {
+            return Ok(Some(self.render_view(&view_matrix, &viewport, xres, yres)?));
+        }
+
+        Ok(None)
+    }
+
     fn get_knob_value(&self, knob_name: &Option<String>) -> f32 {
         if let Some(name) = knob_name && let Some(Symbol::Knob(value)) = self.symbols.get(name) {
             *value
@@ -113,57 +333,338 @@ impl ScriptContext {
     }
 }
 
-pub fn evaluate_commands(commands: Vec<Command>) -> Result<(), Box<dyn Error>> {
-    let mut context = ScriptContext::new();
+fn draw_item(item: &SceneItem, view_matrix: &Matrix, projection: &Projection, picture: &mut Picture) {
+    match item {
+        SceneItem::Edges(edges) => {
+            let mut edges = edges.clone();
+            matrix::multiply(view_matrix, &mut edges);
+
+            let planes = projection.clip_planes(&picture.viewport);
+            let mut clipped = matrix::new();
+            for edge in edges.chunks(2) {
+                if let Some((a, b)) = clip_line(edge[0], edge[1], &planes) {
+                    clipped.push(a);
+                    clipped.push(b);
+                }
+            }
+
+            let clipped = projection.project(&clipped, &picture.viewport, picture.xres, picture.yres);
+            render_edges(&clipped, picture, &DEFAULT_FOREGROUND_COLOR);
+        }
+        SceneItem::Polygons { polygons, shading_mode, lighting_config, reflection_constants } => {
+            let mut polygons = polygons.clone();
+            matrix::multiply(view_matrix, &mut polygons);
 
-    let (num_frames, basename) = animation::first_pass(&commands)?;
+            // lighting happens in view space so the light has to go through the camera too
+            let lighting_config = lighting_config.in_view_space(view_matrix);
 
-    if num_frames == 0 {
-        for command in commands {
+            render_polygons(&polygons, picture, &DEFAULT_FOREGROUND_COLOR, shading_mode, &lighting_config, reflection_constants, projection);
+        }
+        SceneItem::DepthTest(depth_test) => {
+            picture.depth_test = *depth_test;
+        }
+        SceneItem::LineStyle(line_style) => {
+            picture.line_style = *line_style;
+        }
+    }
+}
+
+pub fn evaluate_commands(mut commands: Vec<Command>, options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.frame_all {
+        commands.insert(0, Command::FrameAll { margin: DEFAULT_FRAME_MARGIN });
+    }
+    if let Some(factor) = options.antialias {
+        commands.insert(0, Command::SetAntialias { factor, filter: ResampleFilter::Box });
+    }
+
+    let settings = animation::first_pass(&commands)?;
+
+    if settings.num_frames == 0 {
+        let mut context = ScriptContext::new();
+
+        for command in &commands {
             execute_command(command, &mut context)?;
         }
-    } else {
-        let frame_knob_list = animation::second_pass(&commands, &num_frames)?;
-        let mut gif = Animation::new(context.picture.xres, context.picture.yres);
 
-        for frame in 0..num_frames {
-            context.frame_reset();
+        return Ok(());
+    }
 
-            for (name, value) in &frame_knob_list[frame] {
-                context.set_knob(name.clone(), *value);
-            }
+    let output = options.output.as_ref().unwrap_or(&settings.basename);
+    let (format, _) = OutputFormat::from_path(output)?;
+    let mut frames: Vec<usize> = options.frames(settings.num_frames)?.collect();
 
-            for command in commands.clone() {
-                execute_command(command, &mut context)?;
-            }
+    if let Some(pattern) = &options.assemble {
+        if let OutputFormat::PngSequence { pattern: output_pattern } = &format && output_pattern == pattern {
+            return Err("Cannot assemble a png sequence into itself.".into());
+        }
 
-            if GENERATE_TEMPORARY_FRAME_FILES {
-                context.picture.save_as_file(format!("temp_frames/{}_{:03}.png", basename, frame).as_str())?;
-            } else {
-                gif.add_frame(&context.picture.data);
-            }
+        let num_frames = frames.len();
+        let mut animation: Option<Animation> = None;
+
+        for frame in frames {
+            let file_name = animation::frame_file_name(pattern, frame);
+            let picture = Picture::from_file(&file_name)
+                .map_err(|e| format!("Couldn't read frame '{}': {}", file_name, e))?;
+
+            // the first frame decides how big the animation is
+            let animation = match &mut animation {
+                Some(animation) => animation,
+                None => animation.insert(Animation::new(output, picture.xres, picture.yres, num_frames, &settings)?),
+            };
+
+            animation.add_frame(frame, &picture, settings.delays[frame])?;
+        }
+
+        if let Some(animation) = animation {
+            animation.finish()?;
+        }
+
+        return Ok(());
+    }
+
+    if options.resume {
+        let OutputFormat::PngSequence { pattern } = &format else {
+            return Err("--resume needs the animation to be saved as a png sequence with #s for the frame number, e.g. temp_frames/name_###.png".into());
+        };
+
+        frames.retain(|frame| !Path::new(&animation::frame_file_name(pattern, *frame)).exists());
+
+        if frames.is_empty() {
+            eprintln!("Every frame already exists, nothing to render.");
+            return Ok(());
         }
+    }
+
+    let frame_knob_list = animation::second_pass(&commands, &settings.num_frames)?;
+    let num_frames = frames.len();
+    let mut animation: Option<Animation> = None;
+
+    // the script can change the picture size with screen so the first frame decides how big the animation is
+    let mut write_frame = |frame: usize, picture: Picture| -> Result<(), Box<dyn Error>> {
+        let animation = match &mut animation {
+            Some(animation) => animation,
+            None => animation.insert(Animation::new(output, picture.xres, picture.yres, num_frames, &settings)?),
+        };
 
-        if !GENERATE_TEMPORARY_FRAME_FILES {
-            gif.save_as_file(format!("{}.gif", basename), DEFAULT_ANIMATION_DELAY_MS)?;
+        animation.add_frame(frame, &picture, settings.delays[frame])
+    };
+
+    let symbols = ScriptContext::symbol_table(&commands)?;
+
+    if options.jobs <= 1 {
+        for frame in frames {
+            let picture = render_animation_frame(&commands, &symbols, &frame_knob_list, frame, &settings)?;
+            write_frame(frame, picture)?;
         }
+    } else {
+        render_frames_in_parallel(&commands, &symbols, &frame_knob_list, &frames, &settings, options.jobs, write_frame)?;
+    }
+
+    if let Some(animation) = animation {
+        animation.finish()?;
     }
 
     Ok(())
 }
 
-fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(), Box<dyn Error>> {
-    match command {
+fn render_frame(commands: &[Command], symbols: &HashMap<String, Symbol>, knobs: &HashMap<String, f32>) -> Result<Picture, Box<dyn Error>> {
+    let mut context = ScriptContext::for_frame(symbols, knobs);
+
+    for command in commands {
+        execute_command(command, &mut context)?;
+    }
+
+    match context.render_output(&None)? {
+        Some(picture) => Ok(picture),
+        None => Ok(context.picture),
+    }
+}
+
+fn render_animation_frame(
+    commands: &[Command], symbols: &HashMap<String, Symbol>, frame_knob_list: &[HashMap<String, f32>], frame: usize, settings: &AnimationSettings,
+) -> Result<Picture, Box<dyn Error>> {
+    let Some((samples, shutter)) = settings.motion_blur else {
+        return render_frame(commands, symbols, &frame_knob_list[frame]);
+    };
+
+    // motion blur renders the frame at several points in time before the next one and averages them
+    let mut accumulator: Option<Accumulator> = None;
+
+    for sample in 0..samples {
+        let time = frame as f32 + shutter * sample as f32 / samples as f32;
+        let picture = render_frame(commands, symbols, &animation::knobs_at(frame_knob_list, time))?;
+
+        accumulator.get_or_insert_with(|| Accumulator::new(picture.xres, picture.yres)).add(&picture);
+    }
+
+    Ok(accumulator.ok_or("Motion blur needs at least one sample.")?.to_picture())
+}
+
+fn render_frames_in_parallel<F>(
+    commands: &[Command], symbols: &HashMap<String, Symbol>, frame_knob_list: &[HashMap<String, f32>], frames: &[usize], settings: &AnimationSettings, jobs: usize, mut write_frame: F,
+) -> Result<(), Box<dyn Error>>
+where F: FnMut(usize, Picture) -> Result<(), Box<dyn Error>> {
+    // everything in here works with positions in frames, not the frame numbers themselves
+    let num_frames = frames.len();
+    set_frame_jobs(jobs);
+    let next_frame = AtomicUsize::new(0);
+
+    // workers can't run further ahead than this of the writer so finished frames don't pile up in memory
+    let window = jobs * 2;
+    let written = (Mutex::new(0), Condvar::new());
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<(usize, Result<Picture, String>)>();
+
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (next_frame, written, stop) = (&next_frame, &written, &stop);
+
+            scope.spawn(move || {
+                loop {
+                    let frame = next_frame.fetch_add(1, Ordering::SeqCst);
+                    if frame >= num_frames {
+                        break;
+                    }
+
+                    let (lock, condvar) = written;
+                    let mut count = lock.lock().unwrap();
+                    while frame >= *count + window && !stop.load(Ordering::SeqCst) {
+                        count = condvar.wait(count).unwrap();
+                    }
+                    drop(count);
+
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    // errors aren't Send so they cross over as strings
+                    let result = render_animation_frame(commands, symbols, frame_knob_list, frames[frame], settings)
+                        .map_err(|e| e.to_string());
+                    if sender.send((frame, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        // frames can finish out of order so hold on to them until it's their turn
+        let mut pending: BTreeMap<usize, Picture> = BTreeMap::new();
+        let mut next = 0;
+
+        let result = (|| -> Result<(), Box<dyn Error>> {
+            for (frame, picture) in &receiver {
+                pending.insert(frame, picture?);
+
+                while let Some(picture) = pending.remove(&next) {
+                    write_frame(frames[next], picture)?;
+                    next += 1;
+
+                    let (lock, condvar) = &written;
+                    *lock.lock().unwrap() = next;
+                    condvar.notify_all();
+                }
+            }
+
+            Ok(())
+        })();
+
+        if result.is_err() {
+            // wake up any waiting workers so the scope can join them
+            stop.store(true, Ordering::SeqCst);
+            written.1.notify_all();
+            drop(receiver);
+        }
+
+        result
+    })
+}
+
+fn execute_command(command: &Command, context: &mut ScriptContext) -> Result<(), Box<dyn Error>> {
+    match *command {
         Command::Display => {
-            context.picture.display()?
+            match context.render_output(&None)? {
+                Some(picture) => picture.display()?,
+                None => context.picture.display()?,
+            }
+        }
+
+        Command::Save { ref file_path, ref camera } => {
+            match context.render_output(camera)? {
+                Some(picture) => picture.save_as_file(file_path)?,
+                None => context.picture.save_as_file(file_path)?,
+            }
         }
 
-        Command::Save { file_path } => {
-            context.picture.save_as_file(&file_path)?
+        Command::Views { ref cameras } => {
+            context.finish_framing();
+
+            // as close to a square grid as possible, filled in left to right and top to bottom
+            let columns = (cameras.len() as f32).sqrt().ceil() as usize;
+            let rows = cameras.len().div_ceil(columns);
+            let (width, height) = (context.picture.xres / columns, context.picture.yres / rows);
+            // cells usually aren't the same shape as the picture, so each one shows a bit more of the world instead of squishing it
+            let viewport = context.picture.viewport.fit_aspect(width, height);
+
+            // stereo doesn't apply here, every cell is a plain view
+            let mut cells = vec![];
+            for camera in cameras {
+                cells.push(context.render_view(context.camera(camera)?, &viewport, width, height)?);
+            }
+
+            context.picture.clear();
+            for (i, cell) in cells.iter().enumerate() {
+                context.picture.paste(cell, (i % columns) * width, (i / columns) * height);
+            }
         }
 
         Command::Clear => {
             context.picture.clear();
+            context.clear_scene();
+        }
+
+        Command::SetLineWidth { width } => {
+            context.set_line_style(LineStyle { width, ..context.picture.line_style });
+        }
+
+        Command::SetLineAntialias { antialias } => {
+            context.set_line_style(LineStyle { antialias, ..context.picture.line_style });
+        }
+
+        Command::SetDepthFunc { func } => {
+            context.set_depth_test(DepthTest { func, ..context.picture.depth_test });
+        }
+
+        Command::SetDepthWrite { write } => {
+            context.set_depth_test(DepthTest { write, ..context.picture.depth_test });
+        }
+
+        Command::SetDepthBias { bias, slope_bias } => {
+            context.set_depth_test(DepthTest { bias, slope_bias, ..context.picture.depth_test });
+        }
+
+        Command::SetStereo { stereo } => {
+            context.stereo = stereo;
+        }
+
+        Command::SetDepthOfField { depth_of_field } => {
+            context.depth_of_field = depth_of_field;
+        }
+
+        Command::SetAntialias { factor, filter } => {
+            context.antialias = (factor, filter);
+        }
+
+        Command::FrameAll { margin } => {
+            // a second frame_all finishes the first one so they each get fit on their own
+            context.finish_framing();
+            context.framing = Some((margin, context.scene.len()));
+            // so drawing everything later starts with the settings that are in effect now
+            context.scene.push(SceneItem::DepthTest(context.picture.depth_test));
+            context.scene.push(SceneItem::LineStyle(context.picture.line_style));
         }
 
         Command::Push => {
@@ -174,13 +675,13 @@ fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(),
             context.coordinate_stack.pop();
         }
 
-        Command::Move { a, b, c, knob } => {
-            let multiplier = context.get_knob_value(&knob);
+        Command::Move { a, b, c, ref knob } => {
+            let multiplier = context.get_knob_value(knob);
             context.coordinate_stack.apply_transformation(matrix::translation(a * multiplier, b * multiplier, c * multiplier));
         }
 
-        Command::Scale { a, b, c, knob } => {
-            let multiplier = context.get_knob_value(&knob);
+        Command::Scale { a, b, c, ref knob } => {
+            let multiplier = context.get_knob_value(knob);
             // we need to make sure this goes from 1.0 -> a
             let scale_a = 1.0 + (a - 1.0) * multiplier;
             let scale_b = 1.0 + (b - 1.0) * multiplier;
@@ -188,8 +689,8 @@ fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(),
             context.coordinate_stack.apply_transformation(matrix::dilation(scale_a, scale_b, scale_c));
         }
 
-        Command::Rotate { axis, degrees, knob } => {
-            let multiplier = context.get_knob_value(&knob);
+        Command::Rotate { axis, degrees, ref knob } => {
+            let multiplier = context.get_knob_value(knob);
             context.coordinate_stack.apply_transformation(matrix::rotation(axis, degrees * multiplier));
         }
 
@@ -218,24 +719,24 @@ fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(),
             context.render_polygons(&None)?;
         }
 
-        Command::Box { constants, x, y, z, w, h, d } => {
+        Command::Box { ref constants, x, y, z, w, h, d } => {
             add_box(&mut context.polygons, x, y, z, w, h, d);
-            context.render_polygons(&constants)?;
+            context.render_polygons(constants)?;
         }
 
-        Command::Sphere { constants, x, y, z, r } => {
-            add_sphere(&mut context.polygons, x, y, z, r);
-            context.render_polygons(&constants)?;
+        Command::Sphere { ref constants, x, y, z, r } => {
+            context.polygons.extend_from_slice(&geometry_cache::sphere(x, y, z, r));
+            context.render_polygons(constants)?;
         }
 
-        Command::Torus { constants, x, y, z, r0, r1 } => {
-            add_torus(&mut context.polygons, x, y, z, r0, r1);
-            context.render_polygons(&constants)?;
+        Command::Torus { ref constants, x, y, z, r0, r1 } => {
+            context.polygons.extend_from_slice(&geometry_cache::torus(x, y, z, r0, r1));
+            context.render_polygons(constants)?;
         }
 
-        Command::Mesh { constants, file_path } => {
+        Command::Mesh { ref constants, ref file_path } => {
             handle_mesh(context, file_path)?;
-            context.render_polygons(&constants)?;
+            context.render_polygons(constants)?;
         }
 
         Command::SetLight { r, g, b, x, y, z } => {
@@ -247,45 +748,76 @@ fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(),
             context.lighting_config.ambient_light_color = [r, g, b];
         }
 
-        Command::SetConstants { name, kar, kdr, ksr, kag, kdg, ksg, kab, kdb, ksb } => {
+        Command::SetConstants { ref name, kar, kdr, ksr, kag, kdg, ksg, kab, kdb, ksb } => {
             let constants = ReflectionConstants {
                 ambient: [kar, kag, kab],
                 diffuse: [kdr, kdg, kdb],
                 specular: [ksr, ksg, ksb],
             };
 
-            context.symbols.insert(name, Symbol::Constants(constants));
+            context.symbols.insert(name.clone(), Symbol::Constants(constants));
         }
 
-        Command::SetShading { shading_mode } => {
+        Command::SetShading { ref shading_mode } => {
             context.shading_mode = shading_mode.clone();
         }
 
-        Command::SetCamera { eye_x, eye_y, eye_z, aim_x, aim_y, aim_z } => {
-            // based on opengl's camera transformation matrix
-            // keeps the viewing vector for the math at a consistent <0, 0, 1>
-            let eye = [eye_x, eye_y, eye_z];
-            let aim = [aim_x, aim_y, aim_z];
-            let forward = normalize_vector(&subtract_vectors(&aim, &eye));
-            let up = [0.0, 1.0, 0.0];
+        Command::SetCamera { name: None, eye, aim, up, roll } => {
+            context.camera_matrix = Some(matrix::look_at(&eye, &aim, &up, roll));
+        }
+
+        Command::SetCamera { name: Some(ref name), eye, aim, up, roll } => {
+            // named cameras don't change the current one, they're only for save and views
+            context.symbols.insert(name.clone(), Symbol::Camera(matrix::look_at(&eye, &aim, &up, roll)));
+        }
+
+        Command::LookAt { eye, aim, up, roll } => {
+            // same matrix as the camera but on the stack, so it only lasts until the next pop
+            context.coordinate_stack.apply_transformation(matrix::look_at(&eye, &aim, &up, roll));
+        }
+
+        Command::SetPerspective { fov, near, far } => {
+            context.projection = Projection::Perspective { fov, near, far };
+        }
+
+        Command::SetOrthographic { extent } => {
+            context.projection = Projection::Orthographic;
+
+            // an explicit extent is just the viewport
+            if let Some(extent) = extent {
+                context.picture.viewport = extent;
+            }
+        }
+
+        Command::SetOblique { angle, depth_scale, extent } => {
+            context.projection = Projection::Oblique { angle, depth_scale };
 
-            let right = normalize_vector(&cross_product(&forward, &up));
-            let up_new = cross_product(&right, &forward);
+            if let Some(extent) = extent {
+                context.picture.viewport = extent;
+            }
+        }
 
-            let ex = -dot_product(&right, &eye);
-            let ey = -dot_product(&up_new, &eye);
-            let ez =  dot_product(&forward, &eye);
+        Command::SetScreen { width, height } => {
+            // anything drawn before this is thrown away along with the old picture, so it can't be drawn again later either
+            let old_picture = std::mem::replace(&mut context.picture, Picture::new(width, height, 255, &DEFAULT_BACKGROUND_COLOR));
+            // the default viewport grows around its middle to the new shape, so one unit is still as wide as it is tall
+            context.picture.viewport = if context.viewport_set {
+                old_picture.viewport
+            } else {
+                DEFAULT_VIEWPORT.fit_aspect(width, height)
+            };
+            context.picture.depth_test = old_picture.depth_test;
+            context.picture.line_style = old_picture.line_style;
+            context.clear_scene();
+        }
 
-            context.camera_matrix = vec![
-                [ right[0], right[1], right[2], 0.0 ],
-                [ up_new[0], up_new[1], up_new[2], 0.0 ],
-                [ -forward[0], -forward[1], -forward[2], 0.0 ],
-                [ ex, ey, ez, 1.0 ],
-            ];
+        Command::SetViewport { xmin, ymin, xmax, ymax } => {
+            context.picture.viewport = Viewport { xmin, ymin, xmax, ymax };
+            context.viewport_set = true;
         }
 
-        Command::SetKnob { name, value } => {
-            context.set_knob(name, value);
+        Command::SetKnob { ref name, value } => {
+            context.set_knob(name.clone(), value);
         }
 
         Command::SetAllKnobs { value } => {
@@ -300,14 +832,23 @@ fn execute_command(command: Command, context: &mut ScriptContext) -> Result<(),
 
 fn handle_mesh(
     context: &mut ScriptContext,
-    path: String,
+    path: &str,
 ) -> Result<(), Box<dyn Error>> {
-    let file = Path::new(&path);
-
-    if !file.exists() {
+    if !Path::new(path).exists() {
         return Err(format!("Mesh file '{}' not found", path).into());
     }
 
+    // meshes only get parsed again if the file changes
+    let polygons = geometry_cache::mesh(path, load_mesh)?;
+    context.polygons.extend_from_slice(&polygons);
+
+    Ok(())
+}
+
+fn load_mesh(path: &str) -> Result<Matrix, Box<dyn Error>> {
+    let mut polygons = matrix::new();
+    let file = Path::new(path);
+
     let extension = file
         .extension()
         .and_then(|s| s.to_str())
@@ -320,7 +861,7 @@ fn handle_mesh(
 
     if extension == "obj" {
         let mut vertices: Vec<[f32; 3]> = vec![];
-        for line in read_lines(&path)?.map_while(Result::ok) {
+        for line in read_lines(path)?.map_while(Result::ok) {
             let line = line.trim();
             let parts: Vec<&str> = line.split_whitespace().collect();
 
@@ -332,7 +873,7 @@ fn handle_mesh(
                 let c = parts[3].parse::<usize>()? - 1;
 
                 add_polygon(
-                    &mut context.polygons,
+                    &mut polygons,
                     vertices[a][0], vertices[a][1], vertices[a][2],
                     vertices[b][0], vertices[b][1], vertices[b][2],
                     vertices[c][0], vertices[c][1], vertices[c][2],
@@ -346,7 +887,7 @@ fn handle_mesh(
 
         for polygon in mesh.into_triangle_vec() {
             add_polygon(
-                &mut context.polygons,
+                &mut polygons,
                 polygon.vertices[0][0], polygon.vertices[0][1], polygon.vertices[0][2],
                 polygon.vertices[1][0], polygon.vertices[1][1], polygon.vertices[1][2],
                 polygon.vertices[2][0], polygon.vertices[2][1], polygon.vertices[2][2],
@@ -354,5 +895,93 @@ fn handle_mesh(
         }
     }
 
-    Ok(())
+    Ok(polygons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_keeps_the_default_viewport_undistorted() {
+        let mut context = ScriptContext::new();
+        execute_command(&Command::SetScreen { width: 1000, height: 500 }, &mut context).unwrap();
+
+        let viewport = context.picture.viewport;
+        assert_eq!(viewport.width() / viewport.height(), 2.0);
+        assert_eq!(viewport.center(), DEFAULT_VIEWPORT.center());
+        assert_eq!(viewport.height(), DEFAULT_VIEWPORT.height());
+    }
+
+    #[test]
+    fn screen_keeps_a_viewport_the_script_set() {
+        let mut context = ScriptContext::new();
+        execute_command(&Command::SetViewport { xmin: -1.0, ymin: -1.0, xmax: 1.0, ymax: 1.0 }, &mut context).unwrap();
+        execute_command(&Command::SetScreen { width: 1000, height: 500 }, &mut context).unwrap();
+
+        let viewport = context.picture.viewport;
+        assert_eq!((viewport.xmin, viewport.ymin, viewport.xmax, viewport.ymax), (-1.0, -1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn animation_frames_draw_like_a_static_render() {
+        // the sphere comes before the settings change, so it has to be drawn with the defaults in every frame too
+        let commands = vec![
+            Command::SetConstants { name: "dull".to_string(), kar: 0.2, kdr: 0.5, ksr: 0.0, kag: 0.2, kdg: 0.5, ksg: 0.0, kab: 0.2, kdb: 0.5, ksb: 0.0 },
+            Command::Sphere { constants: None, x: 250.0, y: 250.0, z: 0.0, r: 100.0 },
+            Command::SetShading { shading_mode: ShadingMode::Wireframe },
+            Command::SetLight { r: 255.0, g: 0.0, b: 0.0, x: 1.0, y: 1.0, z: 1.0 },
+            Command::SetPerspective { fov: 60.0, near: 1.0, far: 2000.0 },
+            Command::Box { constants: Some("dull".to_string()), x: 50.0, y: 450.0, z: 0.0, w: 100.0, h: 100.0, d: 100.0 },
+            Command::SetFrames { num_frames: 2 },
+        ];
+
+        let mut context = ScriptContext::new();
+        for command in &commands {
+            execute_command(command, &mut context).unwrap();
+        }
+
+        let symbols = ScriptContext::symbol_table(&commands).unwrap();
+        let frame = render_frame(&commands, &symbols, &HashMap::new()).unwrap();
+
+        assert!(frame.data == context.picture.data);
+    }
+
+    #[test]
+    fn frames_rendered_in_parallel_match_rendering_them_one_at_a_time() {
+        let commands = vec![
+            Command::SetBaseName { name: "spin".to_string() },
+            Command::SetFrames { num_frames: 5 },
+            Command::VaryKnob { knob: "spin".to_string(), start_frame: 0, end_frame: 4, start_val: 0.0, end_val: 1.0 },
+            Command::Push,
+            Command::Move { a: 250.0, b: 250.0, c: 0.0, knob: None },
+            Command::Rotate { axis: matrix::Rotation::Y, degrees: 90.0, knob: Some("spin".to_string()) },
+            Command::Torus { constants: None, x: 0.0, y: 0.0, z: 0.0, r0: 40.0, r1: 150.0 },
+            Command::Pop,
+            Command::Sphere { constants: None, x: 250.0, y: 250.0, z: 0.0, r: 60.0 },
+        ];
+
+        let settings = animation::first_pass(&commands).unwrap();
+        let frame_knob_list = animation::second_pass(&commands, &settings.num_frames).unwrap();
+        let symbols = ScriptContext::symbol_table(&commands).unwrap();
+        let frames: Vec<usize> = (0..settings.num_frames).collect();
+
+        let serial: Vec<Picture> = frames.iter()
+            .map(|&frame| render_animation_frame(&commands, &symbols, &frame_knob_list, frame, &settings).unwrap())
+            .collect();
+
+        let mut parallel = vec![];
+        render_frames_in_parallel(&commands, &symbols, &frame_knob_list, &frames, &settings, 3, |frame, picture| {
+            parallel.push((frame, picture));
+            Ok(())
+        }).unwrap();
+        set_frame_jobs(1);
+
+        assert_eq!(parallel.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(), frames);
+        for ((_, picture), expected) in parallel.iter().zip(&serial) {
+            assert!(picture.data == expected.data);
+        }
+        // the knob has to actually move things for the order to matter
+        assert!(serial[0].data != serial[4].data);
+    }
 }
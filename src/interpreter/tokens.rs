@@ -18,8 +18,24 @@ pub enum Function {
     // GENERAL
     Display,
     Save,
+    Views,
+    FrameAll,
+    SetStereo,
+    SetDepthOfField,
+    SetAntialias,
+    SetDepthFunc,
+    SetDepthWrite,
+    SetDepthBias,
+    SetLineWidth,
+    SetLineAntialias,
     Clear,
     SetCamera,
+    LookAt,
+    SetPerspective,
+    SetOrthographic,
+    SetOblique,
+    SetScreen,
+    SetViewport,
 
     // TRANSFORMATIONS
     Push,
@@ -54,5 +70,11 @@ pub enum Function {
     Tween,
     SetFrames,
     VaryKnob,
+    Oscillate,
+    Wiggle,
     SetAllKnobs,
+    SetFps,
+    SetDelay,
+    SetLoop,
+    SetMotionBlur,
 }
\ No newline at end of file
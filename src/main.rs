@@ -1,21 +1,25 @@
 mod constants;
 mod matrix;
+mod options;
 mod picture;
 mod render;
 mod interpreter;
 mod vector;
 
 use std::{error::Error, env};
+
+use options::Options;
+
 #[show_image::main]
 fn main() -> Result<(), Box<dyn Error>> {
     let arguments: Vec<String> = env::args().collect();
 
-    let path = if arguments.len() < 2 {
-        println!("A path to a script wasn't provided. '{}' was chosen by default.", &constants::DEFAULT_SCRIPT);
-        &String::from(constants::DEFAULT_SCRIPT)
-    } else { &arguments[1] };
+    let options = Options::parse(&arguments)?;
+
+    // custom shaders get registered here with render::shader::register, before the script is parsed
+    // so shading can find them by name (the built in ones are always there)
 
-    interpreter::run_script(path)?;
+    interpreter::run_script(&options)?;
 
     Ok(())
 }
@@ -1,9 +1,12 @@
 #![allow(dead_code)]
 
 type Matrix = Vec<[f32; 4]>;
+type Vector = [f32; 3];
 
 use std::f32::consts::PI;
 
+use crate::vector::{cross_product, dot_product, normalize_vector, subtract_vectors};
+
 #[derive(Clone, Copy, Debug)]
 pub enum Rotation {
     X,
@@ -143,4 +146,117 @@ pub fn rotation(axis: Rotation, degrees: f32) -> Matrix {
     }
 
     transformation_matrix
-}
\ No newline at end of file
+}
+
+// based on opengl's lookat: moves eye to the origin looking down -z with up pointing along +y
+// roll spins the camera counterclockwise around the direction it's looking in, in degrees
+pub fn look_at(eye: &Vector, aim: &Vector, up: &Vector, roll: f32) -> Matrix {
+    let forward = normalize_vector(&subtract_vectors(aim, eye));
+
+    // if up is parallel to forward (looking straight up or down) the cross product is zero
+    // so use whichever axis is furthest from forward instead
+    let mut right = cross_product(&forward, up);
+    if dot_product(&right, &right) < 1e-12 {
+        let fallback = if forward[0].abs() <= forward[1].abs() && forward[0].abs() <= forward[2].abs() {
+            [1.0, 0.0, 0.0]
+        } else if forward[1].abs() <= forward[2].abs() {
+            [0.0, 1.0, 0.0]
+        } else {
+            [0.0, 0.0, 1.0]
+        };
+
+        right = cross_product(&forward, &fallback);
+    }
+
+    let right = normalize_vector(&right);
+    let up = cross_product(&right, &forward);
+
+    let theta = roll * (PI / 180.0);
+    let (sin, cos) = theta.sin_cos();
+    let (right, up) = (
+        [right[0] * cos + up[0] * sin, right[1] * cos + up[1] * sin, right[2] * cos + up[2] * sin],
+        [up[0] * cos - right[0] * sin, up[1] * cos - right[1] * sin, up[2] * cos - right[2] * sin],
+    );
+
+    /*
+        each column dots the point with one of the camera axes
+        rx  ux  -fx  0
+        ry  uy  -fy  0
+        rz  uz  -fz  0
+        -r.e  -u.e  f.e  1
+    */
+
+    vec![
+        [right[0], up[0], -forward[0], 0.0],
+        [right[1], up[1], -forward[1], 0.0],
+        [right[2], up[2], -forward[2], 0.0],
+        [-dot_product(&right, eye), -dot_product(&up, eye), dot_product(&forward, eye), 1.0],
+    ]
+}
+
+// undoes a matrix that only rotates and moves things, like the ones from look_at
+// the rotation part is flipped over its diagonal and the translation gets rotated back and negated
+pub fn invert_rigid(m: &Matrix) -> Matrix {
+    let mut inverse = identity();
+
+    for i in 0..3 {
+        for j in 0..3 {
+            inverse[i][j] = m[j][i];
+        }
+    }
+
+    for j in 0..3 {
+        inverse[3][j] = -(m[3][0] * m[j][0] + m[3][1] * m[j][1] + m[3][2] * m[j][2]);
+    }
+
+    inverse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(m: &Matrix, point: &Vector) -> Vector {
+        let mut points = vec![[point[0], point[1], point[2], 1.0]];
+        multiply(m, &mut points);
+        [points[0][0], points[0][1], points[0][2]]
+    }
+
+    fn assert_close(a: &Vector, b: &Vector) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-3, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn look_at_puts_the_eye_at_the_origin_looking_down_negative_z() {
+        let (eye, aim) = ([100.0, 50.0, -20.0], [-30.0, 80.0, 200.0]);
+        let distance = dot_product(&subtract_vectors(&aim, &eye), &subtract_vectors(&aim, &eye)).sqrt();
+
+        for (up, roll) in [([0.0, 1.0, 0.0], 0.0), ([1.0, 1.0, 0.0], 30.0), ([0.0, 0.0, 1.0], -75.0)] {
+            let m = look_at(&eye, &aim, &up, roll);
+
+            assert_close(&transform(&m, &eye), &[0.0, 0.0, 0.0]);
+            assert_close(&transform(&m, &aim), &[0.0, 0.0, -distance]);
+        }
+    }
+
+    #[test]
+    fn look_at_handles_up_along_the_view_direction() {
+        let m = look_at(&[0.0, 0.0, 0.0], &[0.0, -10.0, 0.0], &[0.0, 1.0, 0.0], 0.0);
+
+        assert_close(&transform(&m, &[0.0, -10.0, 0.0]), &[0.0, 0.0, -10.0]);
+        assert!(m.iter().flatten().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn invert_rigid_undoes_look_at() {
+        let m = look_at(&[100.0, 50.0, -20.0], &[-30.0, 80.0, 200.0], &[0.0, 1.0, 0.0], 40.0);
+        let inverse = invert_rigid(&m);
+
+        for point in [[0.0, 0.0, 0.0], [1.0, 2.0, 3.0], [-250.0, 75.0, 1000.0]] {
+            assert_close(&transform(&inverse, &transform(&m, &point)), &point);
+            assert_close(&transform(&m, &transform(&inverse, &point)), &point);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{error::Error, ops::Range};
+
+use crate::constants::DEFAULT_SCRIPT;
+
+pub struct Options {
+    pub script: String,
+    // overrides the basename of an animation, the extension picks the format
+    pub output: Option<String>,
+    // number of threads animation frames are rendered on
+    pub jobs: usize,
+    // only these frames get rendered, written as A..B, A..=B, A.., ..B or A
+    pub frame_range: Option<(usize, Option<usize>)>,
+    // skip frames whose file already exists in a png sequence
+    pub resume: bool,
+    // build the animation out of an existing png sequence instead of rendering
+    pub assemble: Option<String>,
+    // acts like the script starts with frame_all
+    pub frame_all: bool,
+    // acts like the script starts with antialias N
+    pub antialias: Option<usize>,
+}
+
+impl Options {
+    pub fn parse(arguments: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut script = None;
+        let mut output = None;
+        let mut jobs = 1;
+        let mut frame_range = None;
+        let mut resume = false;
+        let mut assemble = None;
+        let mut frame_all = false;
+        let mut antialias = None;
+
+        let mut iterator = arguments.iter().skip(1);
+
+        while let Some(argument) = iterator.next() {
+            match argument.as_str() {
+                "-o" | "--output" => {
+                    output = Some(iterator.next().ok_or(format!("Expected a path after {}.", argument))?.clone());
+                }
+                "-j" | "--jobs" => {
+                    let value = iterator.next().ok_or(format!("Expected a number after {}.", argument))?;
+                    jobs = value.parse().map_err(|_| format!("Invalid number of jobs: {}", value))?;
+
+                    if jobs == 0 {
+                        return Err("Number of jobs has to be at least 1.".into());
+                    }
+                }
+                "-f" | "--frames" => {
+                    let value = iterator.next().ok_or(format!("Expected a frame range after {}.", argument))?;
+                    frame_range = Some(Options::parse_frame_range(value)?);
+                }
+                "--resume" => {
+                    resume = true;
+                }
+                "--frame-all" => {
+                    frame_all = true;
+                }
+                "-a" | "--antialias" => {
+                    let value = iterator.next().ok_or(format!("Expected a number after {}.", argument))?;
+                    let factor: usize = value.parse().map_err(|_| format!("Invalid antialiasing factor: {}", value))?;
+
+                    if factor == 0 {
+                        return Err("Antialiasing factor has to be at least 1.".into());
+                    }
+                    antialias = Some(factor);
+                }
+                "--assemble" => {
+                    let pattern = iterator.next().ok_or(format!("Expected a png sequence after {}.", argument))?;
+
+                    // the frame number goes where the #s are, without any every frame would be the same file
+                    if !pattern.contains('#') {
+                        return Err(format!(
+                            "Expected a png sequence with #s for the frame number after {}, e.g. temp_frames/name_###.png, got '{}'.",
+                            argument, pattern,
+                        ).into());
+                    }
+                    assemble = Some(pattern.clone());
+                }
+                _ if argument.starts_with('-') => {
+                    return Err(format!("Unknown option: {}", argument).into());
+                }
+                _ => {
+                    if script.is_some() {
+                        return Err(format!("Unexpected argument: {}", argument).into());
+                    }
+                    script = Some(argument.clone());
+                }
+            }
+        }
+
+        let script = script.unwrap_or_else(|| {
+            eprintln!("A path to a script wasn't provided. '{}' was chosen by default.", DEFAULT_SCRIPT);
+            String::from(DEFAULT_SCRIPT)
+        });
+
+        Ok(Self { script, output, jobs, frame_range, resume, assemble, frame_all, antialias })
+    }
+
+    // the end is exclusive, None means the range goes until the last frame
+    fn parse_frame_range(value: &str) -> Result<(usize, Option<usize>), Box<dyn Error>> {
+        let parse = |frame: &str| -> Result<usize, Box<dyn Error>> {
+            frame.parse().map_err(|_| format!("Invalid frame range: {}", value).into())
+        };
+
+        if let Some((start, end)) = value.split_once("..") {
+            let start = if start.is_empty() { 0 } else { parse(start)? };
+
+            let end = if let Some(end) = end.strip_prefix('=') {
+                Some(parse(end)? + 1)
+            } else if end.is_empty() {
+                None
+            } else {
+                Some(parse(end)?)
+            };
+
+            Ok((start, end))
+        } else {
+            let frame = parse(value)?;
+            Ok((frame, Some(frame + 1)))
+        }
+    }
+
+    pub fn frames(&self, num_frames: usize) -> Result<Range<usize>, Box<dyn Error>> {
+        let (start, end) = self.frame_range.unwrap_or((0, None));
+        let end = end.unwrap_or(num_frames);
+
+        if start >= end || end > num_frames {
+            return Err(format!("Frame range {}..{} is outside the animation's {} frames.", start, end, num_frames).into());
+        }
+
+        Ok(start..end)
+    }
+}
@@ -5,17 +5,89 @@ use std::{
     path::Path,
 };
 
-use crate::constants::ENABLE_Z_BUFFER;
+use crate::{
+    constants::{DEFAULT_BACKGROUND_COLOR, DEFAULT_DEPTH_TEST, DEFAULT_LINE_STYLE, DEFAULT_VIEWPORT},
+    render::Viewport,
+};
 use image::{ImageBuffer, Rgb};
 use show_image::{create_window, ImageInfo, ImageView, WindowOptions};
 
+// how the samples of a supersampled picture get weighted when it's shrunk back down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResampleFilter {
+    // plain average of the samples inside each pixel
+    Box,
+    // weights fall off linearly and reach into the neighbouring pixels, a little softer
+    Tent,
+}
+
+// which new pixels win against what's already in the depth buffer, compared by distance from the eye
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthFunc {
+    // closer than what's there
+    Less,
+    // closer or just as close, so later things win ties
+    LessEqual,
+    // further than what's there
+    Greater,
+    // always draw, which is the same as not having a depth buffer
+    Always,
+}
+
+// what the depth buffer gets cleared to, a pixel nothing has been drawn on yet passes every depth func
+// (clearing to the furthest depth there is would work for less, but then greater could never pass)
+const EMPTY_DEPTH: f32 = f32::NAN;
+
+impl DepthFunc {
+    // bigger z is closer to the eye
+    fn passes(&self, z: f32, stored: f32) -> bool {
+        if stored.is_nan() {
+            return true;
+        }
+
+        match self {
+            DepthFunc::Less => z > stored,
+            DepthFunc::LessEqual => z >= stored,
+            DepthFunc::Greater => z < stored,
+            DepthFunc::Always => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthTest {
+    pub func: DepthFunc,
+    // whether pixels that pass also update the depth buffer
+    pub write: bool,
+    // moves everything drawn this far towards the eye before testing, so lines or decals on a surface win against it
+    pub bias: f32,
+    // plus this many times how fast depth changes per pixel across the triangle being drawn,
+    // since a constant isn't enough on faces seen from the side
+    pub slope_bias: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    // in pixels of the final picture
+    pub width: f32,
+    // soften the edges by how much of each pixel the line covers instead of all or nothing
+    pub antialias: bool,
+}
+
+#[derive(Clone)]
 pub struct Picture {
     pub xres: usize,
     pub yres: usize,
     max_color: usize,
     pub data: Vec<u8>, // flat rgb buffer that stores as [r, g, b, r, g, b, ...]
     default_color: (usize, usize, usize),
-    z_buffer: Vec<Vec<f32>>,
+    z_buffer: Vec<f32>, // one depth per pixel, same order as data
+    pub depth_test: DepthTest,
+    pub line_style: LineStyle,
+    // how many of this picture's pixels make up one pixel of the final picture, when it's getting supersampled
+    pub line_scale: f32,
+    // which part of the world this picture shows
+    pub viewport: Viewport,
 }
 
 impl Picture {
@@ -31,7 +103,7 @@ impl Picture {
             }
         }
 
-        let z_buffer = vec![vec![f32::NEG_INFINITY; xres]; yres];
+        let z_buffer = vec![EMPTY_DEPTH; xres * yres];
 
         Self {
             xres,
@@ -40,9 +112,23 @@ impl Picture {
             data,
             default_color,
             z_buffer,
+            depth_test: DEFAULT_DEPTH_TEST,
+            line_style: DEFAULT_LINE_STYLE,
+            line_scale: 1.0,
+            viewport: DEFAULT_VIEWPORT,
         }
     }
 
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(filename)?.to_rgb8();
+        let (xres, yres) = (image.width() as usize, image.height() as usize);
+
+        let mut picture = Picture::new(xres, yres, 255, &DEFAULT_BACKGROUND_COLOR);
+        picture.data = image.into_raw();
+
+        Ok(picture)
+    }
+
     pub fn clear(&mut self) {
         for y in 0..self.yres {
             for x in 0..self.xres {
@@ -53,7 +139,70 @@ impl Picture {
             }
         }
 
-        self.z_buffer = vec![vec![f32::NEG_INFINITY; self.xres]; self.yres];
+        self.z_buffer.fill(EMPTY_DEPTH);
+    }
+
+    // copies other onto this picture with its top left corner at x, y (counting down from the top)
+    pub fn paste(&mut self, other: &Picture, x: usize, y: usize) {
+        for row in 0..other.yres.min(self.yres.saturating_sub(y)) {
+            let width = other.xres.min(self.xres.saturating_sub(x));
+            let from = row * other.xres * 3;
+            let to = ((y + row) * self.xres + x) * 3;
+
+            self.data[to..to + width * 3].copy_from_slice(&other.data[from..from + width * 3]);
+        }
+    }
+
+    // shrinks a picture rendered factor times bigger in both directions back down, one pixel per factor x factor block
+    pub fn downsample(&self, factor: usize, filter: ResampleFilter) -> Picture {
+        let xres = self.xres / factor;
+        let yres = self.yres / factor;
+        let mut picture = Picture::new(xres, yres, self.max_color, &self.default_color);
+        picture.viewport = self.viewport;
+
+        // the filter is separable so the weights for each row and column only get worked out once
+        let weights = |size: usize, samples: usize| -> Vec<Vec<(usize, f32)>> {
+            (0..size).map(|pixel| match filter {
+                ResampleFilter::Box => (pixel * factor..(pixel + 1) * factor).map(|i| (i, 1.0)).collect(),
+                ResampleFilter::Tent => {
+                    let center = (pixel as f32 + 0.5) * factor as f32;
+                    let start = (pixel * factor).saturating_sub(factor / 2 + 1);
+                    let end = ((pixel + 1) * factor + factor / 2 + 1).min(samples);
+
+                    (start..end)
+                        .map(|i| (i, 1.0 - (i as f32 + 0.5 - center).abs() / factor as f32))
+                        .filter(|(_, weight)| *weight > 0.0)
+                        .collect()
+                }
+            }).collect()
+        };
+        let columns = weights(xres, self.xres);
+        let rows = weights(yres, self.yres);
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, column) in columns.iter().enumerate() {
+                let mut sum = [0.0; 3];
+                let mut total = 0.0;
+
+                for &(sy, wy) in row {
+                    for &(sx, wx) in column {
+                        let i = (sy * self.xres + sx) * 3;
+                        let weight = wx * wy;
+                        for (sum, value) in sum.iter_mut().zip(&self.data[i..i + 3]) {
+                            *sum += *value as f32 * weight;
+                        }
+                        total += weight;
+                    }
+                }
+
+                let i = (y * xres + x) * 3;
+                for (value, sum) in picture.data[i..i + 3].iter_mut().zip(sum) {
+                    *value = (sum / total).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        picture
     }
 
     pub fn display(&self) -> Result<(), Box<dyn Error>> {
@@ -83,12 +232,12 @@ impl Picture {
         match extension.as_str() {
             "ppm" => {
                 self.save_ppm(filename)?;
-                println!("{} created.", filename);
+                eprintln!("{} created.", filename);
                 Ok(())
             }
             "png" => {
                 self.save_png(filename)?;
-                println!("{} created.", filename);
+                eprintln!("{} created.", filename);
                 Ok(())
             }
             "" => Err("Cannot save file: please provide a file extension.".into()),
@@ -118,7 +267,87 @@ impl Picture {
         Ok(())
     }
 
+    // a tile covering the whole picture, for drawing on one thread
+    pub fn whole(&mut self) -> Tile<'_> {
+        let line_style = self.scaled_line_style();
+
+        Tile {
+            xres: self.xres,
+            yres: self.yres,
+            first_row: 0,
+            rows: self.yres,
+            data: &mut self.data,
+            z_buffer: &mut self.z_buffer,
+            depth_test: self.depth_test,
+            line_style,
+            depth_slope: 0.0,
+        }
+    }
+
+    // the line style in this picture's pixels
+    pub fn scaled_line_style(&self) -> LineStyle {
+        LineStyle { width: self.line_style.width * self.line_scale, ..self.line_style }
+    }
+
+    // splits the picture into bands of rows that don't overlap, so they can all be drawn into at the same time
+    pub fn tiles(&mut self, rows: usize) -> Vec<Tile<'_>> {
+        let (xres, yres, depth_test, line_style) = (self.xres, self.yres, self.depth_test, self.scaled_line_style());
+
+        self.data.chunks_mut(rows * xres * 3)
+            .zip(self.z_buffer.chunks_mut(rows * xres))
+            .enumerate()
+            .map(|(i, (data, z_buffer))| Tile {
+                xres,
+                yres,
+                first_row: i * rows,
+                rows: z_buffer.len() / xres,
+                data,
+                z_buffer,
+                depth_test,
+                line_style,
+                depth_slope: 0.0,
+            })
+            .collect()
+    }
+
+    pub fn draw_line(&mut self, p0: &[f32; 4], p1: &[f32; 4], color: &(usize, usize, usize)) {
+        self.whole().draw_line(p0, p1, color);
+    }
+}
+
+// part of a picture's color and depth buffers, always whole rows
+// coordinates are still the picture's, anything outside the tile just doesn't get drawn
+pub struct Tile<'a> {
+    pub xres: usize,
+    pub yres: usize,
+    // counted from the top like data
+    first_row: usize,
+    rows: usize,
+    data: &'a mut [u8],
+    z_buffer: &'a mut [f32],
+    depth_test: DepthTest,
+    line_style: LineStyle,
+    // how fast depth changes per pixel across whatever is being drawn, for the slope bias
+    depth_slope: f32,
+}
+
+impl Tile<'_> {
+    pub fn set_depth_slope(&mut self, depth_slope: f32) {
+        self.depth_slope = depth_slope;
+    }
+
+    // smallest and biggest x and y that are inside the tile, with y going up like plot
+    pub fn bounds(&self) -> (isize, isize, isize, isize) {
+        let bottom = self.yres - self.first_row - self.rows;
+        (0, bottom as isize, self.xres as isize - 1, (bottom + self.rows) as isize - 1)
+    }
+
     pub fn plot(&mut self, x: isize, y: isize, z: f32, color: &(usize, usize, usize)) {
+        self.blend(x, y, z, color, 1.0);
+    }
+
+    // like plot but only covers part of the pixel, so the color gets mixed with what's already there
+    pub fn blend(&mut self, x: isize, y: isize, z: f32, color: &(usize, usize, usize), coverage: f32) {
         if x < 0 || y < 0 || x >= self.xres as isize || y >= self.yres as isize {
             return;
         }
@@ -127,22 +356,105 @@ impl Picture {
         let y = y as usize;
         let y = (self.yres - 1) - y;
 
-        let z_truncated = (z * 10000.0) as isize;
-        let buffer_truncated = (self.z_buffer[y][x] * 10000.0) as isize;
+        // other tiles take care of the rest of the picture
+        if y < self.first_row || y >= self.first_row + self.rows {
+            return;
+        }
+
+        let i = (y - self.first_row) * self.xres + x;
+        let z = z + self.depth_test.bias + self.depth_test.slope_bias * self.depth_slope;
 
-        if ENABLE_Z_BUFFER && z_truncated < buffer_truncated {
+        if !self.depth_test.func.passes(z, self.z_buffer[i]) {
             return;
         }
 
-        let i = (y * self.xres + x) * 3;
-        self.data[i] = color.0 as u8;
-        self.data[i + 1] = color.1 as u8;
-        self.data[i + 2] = color.2 as u8;
+        if coverage >= 1.0 {
+            self.data[i * 3] = color.0 as u8;
+            self.data[i * 3 + 1] = color.1 as u8;
+            self.data[i * 3 + 2] = color.2 as u8;
+        } else {
+            for (value, channel) in self.data[i * 3..i * 3 + 3].iter_mut().zip([color.0, color.1, color.2]) {
+                *value = (*value as f32 + (channel as f32 - *value as f32) * coverage).round() as u8;
+            }
+        }
 
-        self.z_buffer[y][x] = z;
+        // a pixel that's mostly background shouldn't hide whatever ends up behind it
+        if self.depth_test.write && coverage >= 0.5 {
+            self.z_buffer[i] = z;
+        }
     }
 
-    pub fn draw_line(&mut self, mut x0: isize, mut y0: isize, mut z0: f32, x1: isize, y1: isize, z1: f32, color: &(usize, usize, usize)) {
+    // p0 and p1 are projected points in pixels with 1/w last, so depth can be blended correctly in perspective
+    pub fn draw_line(&mut self, p0: &[f32; 4], p1: &[f32; 4], color: &(usize, usize, usize)) {
+        if self.line_style.width > 1.0 || self.line_style.antialias {
+            self.draw_wide_line(p0, p1, color);
+        } else {
+            self.draw_thin_line(p0, p1, color);
+        }
+    }
+
+    /*
+        a wide line is every pixel whose center is within half the width of the segment, which rounds off the ends
+        with antialiasing each pixel counts as covered by how far inside that it is, fading out over one pixel
+
+        instead of checking every pixel around the line, this walks along whichever of x and y the line
+        goes further in and only checks the pixels across from the line at each step,
+        which is at most half the width stretched out by the slope (plus a bit for rounding and the ends)
+    */
+    fn draw_wide_line(&mut self, p0: &[f32; 4], p1: &[f32; 4], color: &(usize, usize, usize)) {
+        let radius = self.line_style.width / 2.0;
+        let (dx, dy) = (p1[0] - p0[0], p1[1] - p0[1]);
+        let length_squared = dx * dx + dy * dy;
+
+        // along is the axis that gets walked, across is the other one
+        let steep = dy.abs() > dx.abs();
+        let (along0, across0, along1, across1) = if steep { (p0[1], p0[0], p1[1], p1[0]) } else { (p0[0], p0[1], p1[0], p1[1]) };
+        let slope = if along1 != along0 { (across1 - across0) / (along1 - along0) } else { 0.0 };
+        let reach = radius * (1.0 + slope * slope).sqrt() + 1.5;
+
+        let (min_along, max_along) = (along0.min(along1), along0.max(along1));
+        let start = (min_along - radius - 1.0).floor() as isize;
+        let end = (max_along + radius + 1.0).ceil() as isize;
+
+        for along in start..=end {
+            // the middle of the line at this step, held at the ends past them
+            let center = along as f32 + 0.5;
+            let line = across0 + slope * (center.clamp(min_along, max_along) - along0);
+
+            for across in (line - reach).floor() as isize..=(line + reach).ceil() as isize {
+                let (x, y) = if steep { (across, along) } else { (along, across) };
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+                // closest point on the segment
+                let t = if length_squared > 0.0 {
+                    (((px - p0[0]) * dx + (py - p0[1]) * dy) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = ((px - p0[0] - t * dx).powi(2) + (py - p0[1] - t * dy).powi(2)).sqrt();
+
+                let coverage = if self.line_style.antialias {
+                    (radius + 0.5 - distance).clamp(0.0, 1.0)
+                } else if distance <= radius {
+                    1.0
+                } else {
+                    0.0
+                };
+
+                if coverage > 0.0 {
+                    // z / w and 1 / w go evenly across the screen, z itself only does without perspective
+                    let z = (p0[2] * p0[3] + (p1[2] * p1[3] - p0[2] * p0[3]) * t) / (p0[3] + (p1[3] - p0[3]) * t);
+                    self.blend(x, y, z, color, coverage);
+                }
+            }
+        }
+    }
+
+    // one pixel wide with bresenham
+    fn draw_thin_line(&mut self, p0: &[f32; 4], p1: &[f32; 4], color: &(usize, usize, usize)) {
+        let (mut x0, mut y0) = (p0[0] as isize, p0[1] as isize);
+        let (x1, y1) = (p1[0] as isize, p1[1] as isize);
+
         let dx = (x1 - x0).abs();
         let dy = (y1 - y0).abs();
         let step_x = if x1 > x0 { 1 } else { -1 };
@@ -153,10 +465,19 @@ impl Picture {
         let small_slope = dy <= dx;
         let mut d = if small_slope { a + b / 2 } else { b + a / 2 };
 
+        // z / w and 1 / w go evenly across the screen, z itself only does without perspective
+        let steps = if small_slope { dx } else { dy } as f32 + 1.0;
+        let depth = |step: usize| {
+            let t = step as f32 / steps;
+            let z_over_w = p0[2] * p0[3] + (p1[2] * p1[3] - p0[2] * p0[3]) * t;
+            let inverse_w = p0[3] + (p1[3] - p0[3]) * t;
+            z_over_w / inverse_w
+        };
+        let mut step = 0;
+
         if small_slope {
-            let step_z = (z1 - z0) / (dx as f32 + 1.0);
             loop {
-                self.plot(x0, y0, z0, &color);
+                self.plot(x0, y0, depth(step), color);
                 if x0 == x1 { break; }
                 if d > 0 {
                     y0 += step_y;
@@ -164,12 +485,11 @@ impl Picture {
                 }
                 x0 += step_x;
                 d += a;
-                z0 += step_z;
+                step += 1;
             }
         } else {
-            let step_z = (z1 - z0) / (dy as f32 + 1.0);
             loop {
-                self.plot(x0, y0, z0, &color);
+                self.plot(x0, y0, depth(step), color);
                 if y0 == y1 { break; }
                 if d < 0 {
                     x0 += step_x;
@@ -177,10 +497,79 @@ impl Picture {
                 }
                 y0 += step_y;
                 d += b;
-                z0 += step_z;
+                step += 1;
             }
         }
 
-        self.plot(x0, y0, z0, &color);
+        self.plot(x0, y0, depth(step), color);
+    }
+}
+
+// pictures are only 8 bits per channel so averaging several renders needs more precision
+pub struct Accumulator {
+    pub xres: usize,
+    pub yres: usize,
+    data: Vec<f32>,
+    samples: usize,
+}
+
+impl Accumulator {
+    pub fn new(xres: usize, yres: usize) -> Self {
+        Self {
+            xres,
+            yres,
+            data: vec![0.0; xres * yres * 3],
+            samples: 0,
+        }
+    }
+
+    pub fn add(&mut self, picture: &Picture) {
+        for (sum, value) in self.data.iter_mut().zip(&picture.data) {
+            *sum += *value as f32;
+        }
+
+        self.samples += 1;
+    }
+
+    pub fn to_picture(&self) -> Picture {
+        let mut picture = Picture::new(self.xres, self.yres, 255, &DEFAULT_BACKGROUND_COLOR);
+        let samples = self.samples.max(1) as f32;
+
+        for (value, sum) in picture.data.iter_mut().zip(&self.data) {
+            *value = (sum / samples).round().clamp(0.0, 255.0) as u8;
+        }
+
+        picture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_funcs_compare_with_bigger_z_closer() {
+        assert!(DepthFunc::Less.passes(2.0, 1.0));
+        assert!(!DepthFunc::Less.passes(1.0, 1.0));
+        assert!(!DepthFunc::Less.passes(0.0, 1.0));
+
+        assert!(DepthFunc::LessEqual.passes(2.0, 1.0));
+        assert!(DepthFunc::LessEqual.passes(1.0, 1.0));
+        assert!(!DepthFunc::LessEqual.passes(0.0, 1.0));
+
+        assert!(!DepthFunc::Greater.passes(2.0, 1.0));
+        assert!(!DepthFunc::Greater.passes(1.0, 1.0));
+        assert!(DepthFunc::Greater.passes(0.0, 1.0));
+
+        assert!(DepthFunc::Always.passes(f32::MIN, f32::MAX));
+    }
+
+    #[test]
+    fn empty_depth_passes_every_func() {
+        for func in [DepthFunc::Less, DepthFunc::LessEqual, DepthFunc::Greater, DepthFunc::Always] {
+            assert!(func.passes(f32::MIN, EMPTY_DEPTH));
+            assert!(func.passes(0.0, EMPTY_DEPTH));
+            assert!(func.passes(f32::MAX, EMPTY_DEPTH));
+        }
     }
 }
@@ -0,0 +1,173 @@
+type Plane = [f32; 4];
+
+/*
+    clipping happens in view space, before anything gets projected
+    a plane is stored as [a, b, c, d] and a point is inside when ax + by + cz + d >= 0
+
+    triangles are clipped with sutherland-hodgman: go around the polygon once per plane,
+    keep the points that are inside and add a new point wherever an edge crosses the plane
+    the new points get every other attribute (normals etc.) interpolated by the same amount
+*/
+
+pub fn distance(plane: &Plane, point: &[f32; 4]) -> f32 {
+    plane[0] * point[0] + plane[1] * point[1] + plane[2] * point[2] + plane[3]
+}
+
+pub fn is_inside(planes: &[Plane], point: &[f32; 4]) -> bool {
+    planes.iter().all(|plane| distance(plane, point) >= 0.0)
+}
+
+// position gets the view space point out of a vertex, lerp blends two vertices by t
+pub fn clip_polygon<V, P, L>(polygon: Vec<V>, planes: &[Plane], position: P, lerp: L) -> Vec<V>
+where V: Clone, P: Fn(&V) -> [f32; 4], L: Fn(&V, &V, f32) -> V {
+    // nearly everything is completely inside so don't bother building a new list
+    if polygon.iter().all(|vertex| is_inside(planes, &position(vertex))) {
+        return polygon;
+    }
+
+    let mut output = polygon;
+
+    for plane in planes {
+        if output.is_empty() {
+            break;
+        }
+
+        let input = std::mem::take(&mut output);
+
+        for i in 0..input.len() {
+            let current = &input[i];
+            let previous = &input[(i + input.len() - 1) % input.len()];
+
+            let d_current = distance(plane, &position(current));
+            let d_previous = distance(plane, &position(previous));
+
+            // the edge crosses the plane so add the point where it does
+            if (d_current >= 0.0) != (d_previous >= 0.0) {
+                let t = d_previous / (d_previous - d_current);
+                output.push(lerp(previous, current, t));
+            }
+
+            if d_current >= 0.0 {
+                output.push(current.clone());
+            }
+        }
+    }
+
+    output
+}
+
+// returns the part of the segment inside every plane, if there is any
+pub fn clip_line(mut a: [f32; 4], mut b: [f32; 4], planes: &[Plane]) -> Option<([f32; 4], [f32; 4])> {
+    for plane in planes {
+        let d_a = distance(plane, &a);
+        let d_b = distance(plane, &b);
+
+        if d_a < 0.0 && d_b < 0.0 {
+            return None;
+        }
+
+        if d_a < 0.0 || d_b < 0.0 {
+            let t = d_a / (d_a - d_b);
+            let crossing = lerp_point(&a, &b, t);
+
+            if d_a < 0.0 {
+                a = crossing;
+            } else {
+                b = crossing;
+            }
+        }
+    }
+
+    Some((a, b))
+}
+
+pub fn lerp_point(a: &[f32; 4], b: &[f32; 4], t: f32) -> [f32; 4] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::lerp_vectors;
+
+    // keeps everything at least one unit in front of the eye, like a near plane
+    const NEAR: Plane = [0.0, 0.0, -1.0, -1.0];
+
+    type Vertex = ([f32; 4], [f32; 3]);
+
+    fn clip(triangle: Vec<Vertex>) -> Vec<Vertex> {
+        clip_polygon(triangle, &[NEAR], |vertex| vertex.0, |a, b, t| (lerp_point(&a.0, &b.0, t), lerp_vectors(&a.1, &b.1, t)))
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn triangle_with_one_corner_behind_near_becomes_a_quad() {
+        let clipped = clip(vec![
+            ([0.0, 0.0, -3.0, 1.0], [0.0, 0.0, 1.0]),
+            ([4.0, 0.0, -3.0, 1.0], [1.0, 0.0, 0.0]),
+            ([0.0, 4.0, 1.0, 1.0], [0.0, 1.0, 0.0]),
+        ]);
+
+        assert_eq!(clipped.len(), 4);
+        // both new corners are halfway along their edges, depth and normal included
+        assert_close(&clipped[0].0, &[0.0, 2.0, -1.0, 1.0]);
+        assert_close(&clipped[0].1, &[0.0, 0.5, 0.5]);
+        assert_close(&clipped[1].0, &[0.0, 0.0, -3.0, 1.0]);
+        assert_close(&clipped[2].0, &[4.0, 0.0, -3.0, 1.0]);
+        assert_close(&clipped[3].0, &[2.0, 2.0, -1.0, 1.0]);
+        assert_close(&clipped[3].1, &[0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn triangle_with_two_corners_behind_near_stays_a_triangle() {
+        let clipped = clip(vec![
+            ([0.0, 0.0, -5.0, 1.0], [0.0, 0.0, 1.0]),
+            ([4.0, 0.0, 3.0, 1.0], [1.0, 0.0, 0.0]),
+            ([0.0, 4.0, 3.0, 1.0], [0.0, 1.0, 0.0]),
+        ]);
+
+        assert_eq!(clipped.len(), 3);
+        assert_close(&clipped[0].0, &[0.0, 2.0, -1.0, 1.0]);
+        assert_close(&clipped[0].1, &[0.0, 0.5, 0.5]);
+        assert_close(&clipped[1].0, &[0.0, 0.0, -5.0, 1.0]);
+        assert_close(&clipped[2].0, &[2.0, 0.0, -1.0, 1.0]);
+        assert_close(&clipped[2].1, &[0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn triangles_entirely_on_one_side_are_kept_or_dropped_whole() {
+        let inside = vec![
+            ([0.0, 0.0, -2.0, 1.0], [0.0, 0.0, 1.0]),
+            ([4.0, 0.0, -2.0, 1.0], [0.0, 0.0, 1.0]),
+            ([0.0, 4.0, -2.0, 1.0], [0.0, 0.0, 1.0]),
+        ];
+        let behind = inside.iter().map(|&(point, normal)| ([point[0], point[1], 2.0, 1.0], normal)).collect();
+
+        assert_eq!(clip(inside.clone()), inside);
+        assert!(clip(behind).is_empty());
+    }
+
+    #[test]
+    fn line_crossing_a_plane_gets_cut_where_it_crosses() {
+        let (a, b) = ([0.0, 0.0, -5.0, 1.0], [8.0, 4.0, 3.0, 1.0]);
+
+        let (start, end) = clip_line(a, b, &[NEAR]).unwrap();
+        assert_close(&start, &a);
+        assert_close(&end, &[4.0, 2.0, -1.0, 1.0]);
+
+        // either end can be the one that gets moved
+        let (start, end) = clip_line(b, a, &[NEAR]).unwrap();
+        assert_close(&start, &[4.0, 2.0, -1.0, 1.0]);
+        assert_close(&end, &a);
+
+        assert!(clip_line([0.0, 0.0, 2.0, 1.0], [8.0, 4.0, 3.0, 1.0], &[NEAR]).is_none());
+    }
+}
@@ -0,0 +1,42 @@
+use std::f32::consts::PI;
+
+use crate::matrix;
+
+type Matrix = Vec<[f32; 4]>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct DepthOfField {
+    // diameter of the lens, bigger means blurrier
+    pub aperture: f32,
+    // distance in front of the eye that stays sharp
+    pub focus_distance: f32,
+    pub samples: usize,
+}
+
+impl DepthOfField {
+    /*
+        the view for one sample moves the eye to a spot on the lens and then shears the scene back
+        so that everything at the focus distance lands exactly where it did before:
+        x' = x - dx * (z + focus) / focus, which is x on the focal plane (z = -focus) and x - dx at the eye
+        everything in front of or behind that plane moves a different amount in every sample and gets blurred
+
+        this only makes sense with perspective, where the eye is a point everything is seen from (see run_script.rs)
+
+        the spots are spread out over the lens in a sunflower spiral so it's even and the same every frame
+    */
+    pub fn sample_view(&self, view_matrix: &Matrix, sample: usize) -> Matrix {
+        let golden_angle = PI * (3.0 - 5.0_f32.sqrt());
+        let radius = self.aperture / 2.0 * ((sample as f32 + 0.5) / self.samples as f32).sqrt();
+        let theta = sample as f32 * golden_angle;
+        let (dx, dy) = (radius * theta.cos(), radius * theta.sin());
+
+        let mut lens = matrix::translation(-dx, -dy, 0.0);
+        lens[2][0] = -dx / self.focus_distance;
+        lens[2][1] = -dy / self.focus_distance;
+
+        let mut sample_view = view_matrix.clone();
+        matrix::multiply(&lens, &mut sample_view);
+
+        sample_view
+    }
+}
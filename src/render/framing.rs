@@ -0,0 +1,107 @@
+use crate::matrix;
+
+use super::{Projection, Viewport, projection::PARALLEL_EYE_DISTANCE};
+
+type Matrix = Vec<[f32; 4]>;
+type Vector = [f32; 3];
+
+/*
+    frame_all moves and scales everything drawn after it so it fills the picture
+    the fit is worked out in view space where the viewport is just a rectangle in x and y,
+    then it gets turned back into world space (see run_script.rs) so named cameras see the same scene
+*/
+
+// smallest and biggest x, y and z out of all the points, None if there aren't any
+pub fn bounds<'a>(points: impl Iterator<Item = &'a [f32; 4]>) -> Option<(Vector, Vector)> {
+    let mut bounds: Option<(Vector, Vector)> = None;
+
+    for point in points {
+        let (min, max) = bounds.get_or_insert(([point[0], point[1], point[2]], [point[0], point[1], point[2]]));
+
+        for i in 0..3 {
+            min[i] = min[i].min(point[i]);
+            max[i] = max[i].max(point[i]);
+        }
+    }
+
+    bounds
+}
+
+// margin is how much of the picture to leave empty on each side, as a fraction of its size
+pub fn fit(bounds: &(Vector, Vector), projection: &Projection, viewport: &Viewport, margin: f32) -> Matrix {
+    let (min, max) = bounds;
+
+    // oblique pushes x and y over by the depth, so fit the box as it ends up in the picture
+    // (kx and ky are 0 for everything else so this is just the box)
+    let (kx, ky) = projection.shear();
+    let cz = (min[2] + max[2]) / 2.0;
+    let (x_min, x_max) = (min[0] - f32::max(kx * min[2], kx * max[2]), max[0] - f32::min(kx * min[2], kx * max[2]));
+    let (y_min, y_max) = (min[1] - f32::max(ky * min[2], ky * max[2]), max[1] - f32::min(ky * min[2], ky * max[2]));
+    let center = [(x_min + x_max) / 2.0 + kx * cz, (y_min + y_max) / 2.0 + ky * cz, cz];
+
+    // the same scale for every axis so nothing gets squished, and a flat scene (everything at one x or y) still works
+    let fill = 1.0 - 2.0 * margin;
+    let width = (x_max - x_min).max(f32::EPSILON);
+    let height = (y_max - y_min).max(f32::EPSILON);
+    let scale = f32::min(viewport.width() * fill / width, viewport.height() * fill / height);
+
+    let target = match *projection {
+        // x and y are viewport units already, it goes where the default camera would see the z = 0 plane
+        Projection::Orthographic | Projection::Oblique { .. } => {
+            [-kx * PARALLEL_EYE_DISTANCE, -ky * PARALLEL_EYE_DISTANCE, -PARALLEL_EYE_DISTANCE]
+        }
+        // put the front of the box where one unit is one viewport unit, everything behind it only gets smaller
+        Projection::Perspective { fov, .. } => {
+            let distance = Projection::focal_length(fov, viewport);
+            [0.0, 0.0, -distance - (max[2] - center[2]) * scale]
+        }
+    };
+
+    let mut transform = matrix::translation(-center[0], -center[1], -center[2]);
+    matrix::multiply(&matrix::dilation(scale, scale, scale), &mut transform);
+    matrix::multiply(&matrix::translation(target[0], target[1], target[2]), &mut transform);
+
+    transform
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: Viewport = Viewport { xmin: 0.0, ymin: 0.0, xmax: 500.0, ymax: 500.0 };
+
+    #[test]
+    fn bounds_cover_every_point() {
+        let points = [[-10.0, 15.0, 0.0, 1.0], [30.0, -5.0, 20.0, 1.0], [0.0, 0.0, 10.0, 1.0]];
+
+        assert_eq!(bounds(points.iter()), Some(([-10.0, -5.0, 0.0], [30.0, 15.0, 20.0])));
+        assert_eq!(bounds([].iter()), None);
+    }
+
+    #[test]
+    fn fit_fills_the_picture_minus_the_margin_around_its_middle() {
+        // twice as wide as it is tall, so the width is what runs into the margin
+        let scene = ([-10.0, -5.0, 0.0], [30.0, 15.0, 20.0]);
+        let (min, max) = scene;
+
+        for projection in [Projection::Orthographic, Projection::Perspective { fov: 60.0, near: 1.0, far: 10000.0 }] {
+            let mut corners: Matrix = (0..8)
+                .map(|i| [
+                    if i & 1 == 0 { min[0] } else { max[0] },
+                    if i & 2 == 0 { min[1] } else { max[1] },
+                    if i & 4 == 0 { min[2] } else { max[2] },
+                    1.0,
+                ])
+                .collect();
+            matrix::multiply(&fit(&scene, &projection, &VIEWPORT, 0.1), &mut corners);
+
+            let pixels = projection.project(&corners, &VIEWPORT, 500, 500);
+            let (low, high) = bounds(pixels.iter()).unwrap();
+
+            assert!((high[0] - low[0] - 400.0).abs() < 0.01, "{:?} {:?}", projection, (low, high));
+            assert!(high[1] - low[1] < 400.0, "{:?} {:?}", projection, (low, high));
+            assert!(((low[0] + high[0]) / 2.0 - 250.0).abs() < 0.01, "{:?} {:?}", projection, (low, high));
+            assert!(((low[1] + high[1]) / 2.0 - 250.0).abs() < 0.01, "{:?} {:?}", projection, (low, high));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    hash::Hash,
+    path::PathBuf,
+    sync::{Arc, LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use crate::constants::GEOMETRY_CACHE_SIZE;
+use super::polygon_list::{add_sphere, add_torus};
+
+type PolygonList = Vec<[f32; 4]>;
+type MeshEntry = (SystemTime, Arc<PolygonList>);
+
+// everything in here is stored in object space, the coordinate stack gets applied afterwards
+// so the same entry can be reused by every frame of an animation (and every thread rendering them)
+
+// floats don't implement Hash so the parameters are keyed by their bits
+#[derive(Hash, PartialEq, Eq)]
+enum PrimitiveKey {
+    Sphere([u32; 4]),
+    Torus([u32; 5]),
+}
+
+// a map that forgets whatever was used longest ago once it's full,
+// so an animation sweeping the radius of a sphere doesn't keep every sphere it ever made
+struct Cache<K, V> {
+    entries: HashMap<K, (u64, V)>,
+    // goes up on every lookup, each entry remembers when it was last used
+    clock: u64,
+}
+
+impl<K: Hash + Eq, V: Clone> Cache<K, V> {
+    fn new() -> Self {
+        Self { entries: HashMap::new(), clock: 0 }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.clock += 1;
+        let (used, value) = self.entries.get_mut(key)?;
+        *used = self.clock;
+
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.entries.len() >= GEOMETRY_CACHE_SIZE && !self.entries.contains_key(&key) {
+            // every entry was used at a different time so this only throws out one
+            if let Some(oldest) = self.entries.values().map(|(used, _)| *used).min() {
+                self.entries.retain(|_, (used, _)| *used != oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.entries.insert(key, (self.clock, value));
+    }
+}
+
+static MESHES: LazyLock<Mutex<Cache<PathBuf, MeshEntry>>> = LazyLock::new(|| Mutex::new(Cache::new()));
+static PRIMITIVES: LazyLock<Mutex<Cache<PrimitiveKey, Arc<PolygonList>>>> = LazyLock::new(|| Mutex::new(Cache::new()));
+
+pub fn mesh<F>(path: &str, load: F) -> Result<Arc<PolygonList>, Box<dyn Error>>
+where F: FnOnce(&str) -> Result<PolygonList, Box<dyn Error>> {
+    let key = PathBuf::from(path);
+    // if the file gets edited in between renders we want the new version
+    let modified = fs::metadata(path)?.modified()?;
+
+    if let Some((cached_modified, polygons)) = MESHES.lock().unwrap().get(&key) && cached_modified == modified {
+        return Ok(polygons);
+    }
+
+    // parse without holding the lock so other threads can keep reading the cache
+    let polygons = Arc::new(load(path)?);
+    MESHES.lock().unwrap().insert(key, (modified, Arc::clone(&polygons)));
+
+    Ok(polygons)
+}
+
+pub fn sphere(cx: f32, cy: f32, cz: f32, r: f32) -> Arc<PolygonList> {
+    let key = PrimitiveKey::Sphere([cx.to_bits(), cy.to_bits(), cz.to_bits(), r.to_bits()]);
+
+    primitive(key, |m| add_sphere(m, cx, cy, cz, r))
+}
+
+pub fn torus(cx: f32, cy: f32, cz: f32, r0: f32, r1: f32) -> Arc<PolygonList> {
+    let key = PrimitiveKey::Torus([cx.to_bits(), cy.to_bits(), cz.to_bits(), r0.to_bits(), r1.to_bits()]);
+
+    primitive(key, |m| add_torus(m, cx, cy, cz, r0, r1))
+}
+
+fn primitive<F>(key: PrimitiveKey, generate: F) -> Arc<PolygonList>
+where F: FnOnce(&mut PolygonList) {
+    if let Some(polygons) = PRIMITIVES.lock().unwrap().get(&key) {
+        return polygons;
+    }
+
+    let mut polygons = vec![];
+    generate(&mut polygons);
+    let polygons = Arc::new(polygons);
+
+    PRIMITIVES.lock().unwrap().insert(key, Arc::clone(&polygons));
+
+    polygons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_forgets_what_was_used_longest_ago() {
+        let mut cache = Cache::new();
+        for i in 0..GEOMETRY_CACHE_SIZE {
+            cache.insert(i, i);
+        }
+
+        // reading the first one makes the second one the oldest
+        assert_eq!(cache.get(&0), Some(0));
+        cache.insert(GEOMETRY_CACHE_SIZE, GEOMETRY_CACHE_SIZE);
+
+        assert_eq!(cache.entries.len(), GEOMETRY_CACHE_SIZE);
+        assert_eq!(cache.get(&0), Some(0));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&GEOMETRY_CACHE_SIZE), Some(GEOMETRY_CACHE_SIZE));
+    }
+
+    #[test]
+    fn sweeping_a_sphere_doesnt_grow_the_cache() {
+        for i in 0..GEOMETRY_CACHE_SIZE * 2 {
+            sphere(0.0, 0.0, 0.0, 1.0 + i as f32);
+        }
+
+        assert!(PRIMITIVES.lock().unwrap().entries.len() <= GEOMETRY_CACHE_SIZE);
+    }
+}
@@ -9,8 +9,23 @@ use crate::{
 pub struct LightingConfig {
     pub ambient_light_color: [f32; 3],
     pub point_light_color: [f32; 3],
+    // in world space, the camera gets applied to it right before drawing
     pub point_light_vector: [f32; 3],
-    // note: viewer vector is always <0, 0, 1> so all the math for backface culling and lighting is hardcoded
+}
+
+impl LightingConfig {
+    // rotates the light the same way the camera rotates everything else so it stays put in the world
+    // it's a direction so the camera's translation doesn't matter
+    pub fn in_view_space(&self, view_matrix: &[[f32; 4]]) -> LightingConfig {
+        let v = self.point_light_vector;
+        let mut config = *self;
+
+        for (j, component) in config.point_light_vector.iter_mut().enumerate() {
+            *component = view_matrix[0][j] * v[0] + view_matrix[1][j] * v[1] + view_matrix[2][j] * v[2];
+        }
+
+        config
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -20,13 +35,15 @@ pub struct ReflectionConstants {
     pub specular: [f32; 3],
 }
 
-pub fn get_illumination(normal: &Vector, config: &LightingConfig, constants: &ReflectionConstants) -> (usize, usize, usize) {
+// view points from the surface to the eye, which is <0, 0, 1> everywhere for orthographic
+pub fn get_illumination(normal: &Vector, view: &Vector, config: &LightingConfig, constants: &ReflectionConstants) -> (usize, usize, usize) {
     let normal = &normalize_vector(&normal);
+    let view = &normalize_vector(view);
     let point_light_vector = normalize_vector(&config.point_light_vector);
 
     let ambient = get_ambient(&config.ambient_light_color, &constants.ambient);
     let diffuse = get_diffuse(normal, &point_light_vector, &config.point_light_color, &constants.diffuse);
-    let specular = get_specular(normal, &point_light_vector, &config.point_light_color, &constants.specular);
+    let specular = get_specular(normal, &point_light_vector, view, &config.point_light_color, &constants.specular);
 
     clamp_color([
         ambient[0] + diffuse[0] + specular[0],
@@ -54,20 +71,29 @@ pub fn get_diffuse(normal: &Vector, light_vector: &Vector, light_color: &Vector,
     ]
 }
 
-pub fn get_specular(normal: &Vector, light_vector: &Vector, light_color: &Vector, specular_constant: &Vector) -> Vector {
+pub fn get_specular(normal: &Vector, light_vector: &Vector, view: &Vector, light_color: &Vector, specular_constant: &Vector) -> Vector {
     // i_specular = point color * specular reflection constant * (normalized reflection dot view)^exp
     // where exp > 1
     // normalized reflection = [2 * normalized normal * (normalized normal dot normalized light) - normalized light]
-    
-    // for normalized reflection dot view:
-    // since view just <0, 0, 1>, we can be lazy and take the z value, raise it to exp, and call it r_z
     let n_dot_l = f32::max(0.0, dot_product(normal, light_vector));
-    let r_z = f32::max(0.0, 2.0 * normal[2] * n_dot_l - light_vector[2]).powf(SPECULAR_EXPONENT);
+    let reflection = [
+        2.0 * normal[0] * n_dot_l - light_vector[0],
+        2.0 * normal[1] * n_dot_l - light_vector[1],
+        2.0 * normal[2] * n_dot_l - light_vector[2],
+    ];
+
+    // a surface facing away from the light can't have a highlight, otherwise the reflection is just -light
+    // and a camera on the dark side would see one
+    let r_dot_v = if n_dot_l > 0.0 {
+        f32::max(0.0, dot_product(&reflection, view)).powf(SPECULAR_EXPONENT)
+    } else {
+        0.0
+    };
 
     [
-        light_color[0] * specular_constant[0] * r_z,
-        light_color[1] * specular_constant[1] * r_z,
-        light_color[2] * specular_constant[2] * r_z,
+        light_color[0] * specular_constant[0] * r_dot_v,
+        light_color[1] * specular_constant[1] * r_dot_v,
+        light_color[2] * specular_constant[2] * r_dot_v,
     ]
 }
 
@@ -1,7 +1,16 @@
 pub mod lighting;
+pub mod rasterizer;
 pub mod scan_line;
+pub mod shader;
 pub mod edge_list;
 pub mod polygon_list;
+pub mod geometry_cache;
+pub mod projection;
+pub mod clipping;
+pub mod framing;
+pub mod stereo;
+pub mod depth_of_field;
 
-pub use crate::picture::Picture;
-pub use lighting::{LightingConfig, ReflectionConstants, get_illumination};
+pub use crate::picture::{Picture, Tile};
+pub use lighting::{LightingConfig, ReflectionConstants};
+pub use projection::{Projection, Viewport};
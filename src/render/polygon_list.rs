@@ -1,27 +1,65 @@
-use rand::Rng;
-
 type PolygonList = Vec<[f32; 4]>;
 type Vector = [f32; 3];
 
 use std::{
     f32::consts::PI,
     collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 use crate::{
-    constants::{CUBE, ENABLE_BACK_FACE_CULLING, PARAMETRIC_STEPS, ShadingMode},
+    constants::{CUBE, EDGE_SLOPE_BIAS, ENABLE_BACK_FACE_CULLING, PARALLEL_TRIANGLES, PARAMETRIC_STEPS, TILE_ROWS},
     matrix::add_point,
-    vector::{add_vectors, cross_product, normalize_vector}
+    vector::{add_vectors, cross_product, lerp_vectors, normalize_vector, subtract_vectors}
 };
 use super::{
+    clipping::{clip_polygon, lerp_point},
     scan_line,
-    Picture, LightingConfig, ReflectionConstants, get_illumination,
+    shader::{Shader, ShadingMode, Uniforms, Varyings, Vertex},
+    Picture, Tile, LightingConfig, Projection, ReflectionConstants,
 };
 
+// how many frames are being drawn at the same time, they split the cores between them for drawing tiles
+static FRAME_JOBS: AtomicUsize = AtomicUsize::new(1);
+
+pub fn set_frame_jobs(jobs: usize) {
+    FRAME_JOBS.store(jobs.max(1), Ordering::Relaxed);
+}
+
+// a triangle in pixels that's facing the camera, with what came out of the shader's vertex stage
+// wireframe doesn't have a shader so there's nothing to blend
+struct ScreenTriangle {
+    polygon: [[f32; 4]; 3],
+    varyings: Option<[Varyings; 3]>,
+}
+
+impl ScreenTriangle {
+    // how much depth changes going one pixel across the triangle, in whichever of x or y it changes faster
+    // never more than the whole depth of the triangle, so slivers seen almost edge on don't get a huge bias
+    fn depth_slope(&self) -> f32 {
+        let [a, b, c] = self.polygon;
+        let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let normal = cross_product(&u, &v);
+
+        let z_range = a[2].max(b[2]).max(c[2]) - a[2].min(b[2]).min(c[2]);
+        if normal[2].abs() <= f32::EPSILON {
+            return z_range;
+        }
+
+        (normal[0].abs().max(normal[1].abs()) / normal[2].abs()).min(z_range)
+    }
+}
+
 fn vector_to_key(vector: &[f32; 4]) -> (isize, isize, isize) {
     (vector[0].round() as isize, vector[1].round() as isize, vector[2].round() as isize)
 }
 
+fn point_to_vector(point: &[f32; 4]) -> Vector {
+    [point[0], point[1], point[2]]
+}
+
 pub fn add_polygon(m: &mut PolygonList, x0: f32, y0: f32, z0: f32, x1: f32, y1: f32, z1: f32, x2: f32, y2: f32, z2: f32) {
     add_point(m, x0, y0, z0, 1.0);
     add_point(m, x1, y1, z1, 1.0);
@@ -30,17 +68,45 @@ pub fn add_polygon(m: &mut PolygonList, x0: f32, y0: f32, z0: f32, x1: f32, y1:
 
 pub fn render_polygons(
     m: &PolygonList, picture: &mut Picture, color: &(usize, usize, usize),
-    shading_mode: &ShadingMode, lighting_config: &LightingConfig, reflection_constants: &ReflectionConstants
+    shading_mode: &ShadingMode, lighting_config: &LightingConfig, reflection_constants: &ReflectionConstants, projection: &Projection,
 ) {
+    let uniforms = Uniforms { color, lighting_config, reflection_constants, projection };
+    let shader = match shading_mode {
+        ShadingMode::Wireframe => None,
+        ShadingMode::Shader(shader) | ShadingMode::ShadedEdges { shader, .. } => Some(shader.as_ref()),
+    };
+    let triangles = screen_triangles(m, picture, shader, &uniforms);
+
+    draw_triangles(picture, &triangles, shader, &uniforms);
+
+    // the faces are all in the depth buffer now, so only the edges that aren't behind one get drawn
+    if let ShadingMode::ShadedEdges { color, bias, .. } = shading_mode {
+        let depth_test = picture.depth_test;
+        picture.depth_test.bias += bias;
+        // line pixels can land up to half a pixel (plus half the width) off the actual edge, where the face is that much deeper
+        picture.depth_test.slope_bias += EDGE_SLOPE_BIAS + picture.scaled_line_style().width / 2.0;
+
+        draw_triangles(picture, &triangles, None, &Uniforms { color, ..uniforms });
+
+        picture.depth_test = depth_test;
+    }
+}
+
+// clips every polygon in m and projects what's left, dropping whatever faces away
+fn screen_triangles(m: &PolygonList, picture: &Picture, shader: Option<&dyn Shader>, uniforms: &Uniforms) -> Vec<ScreenTriangle> {
+    let smooth_normals = shader.is_some_and(|shader| shader.smooth_normals());
+
+    // m is in view space, which is where clipping and the normals for lighting happen
+    // culling and drawing happen on the projected points, which are the same thing for orthographic
+
     // for gouraud and phong shading
     // we need to keep a hash to get the average normal for every polygon that contains this vertex
     // instead of getting averages we can sum up all the vectors and then normalize it at the end
     // we need them to be normalized for lighting anyway
     let mut vertex_normals: HashMap<(isize, isize, isize), Vector> = HashMap::new();
 
-    match shading_mode {
-        ShadingMode::Gouraud | ShadingMode::Phong => {
-            for polygon in m.chunks(3) {
+    if smooth_normals {
+        for polygon in m.chunks(3) {
             let a = [
                 polygon[1][0] - polygon[0][0],
                 polygon[1][1] - polygon[0][1],
@@ -66,95 +132,175 @@ pub fn render_polygons(
                 *normal = normalize_vector(normal);
             }
         }
+    }
 
+    let planes = uniforms.projection.clip_planes(&picture.viewport);
+    let mut triangles = vec![];
+
+    for view_polygon in m.chunks(3) {
+        // clipping doesn't change which way the face points so flat shading can use the whole triangle
+        let face_normal = normalize_vector(&cross_product(
+            &subtract_vectors(&point_to_vector(&view_polygon[1]), &point_to_vector(&view_polygon[0])),
+            &subtract_vectors(&point_to_vector(&view_polygon[2]), &point_to_vector(&view_polygon[0])),
+        ));
+
+        let vertex_normals = if smooth_normals {
+            [
+                *vertex_normals.get(&vector_to_key(&view_polygon[0])).unwrap(),
+                *vertex_normals.get(&vector_to_key(&view_polygon[1])).unwrap(),
+                *vertex_normals.get(&vector_to_key(&view_polygon[2])).unwrap(),
+            ]
+        } else {
+            [face_normal; 3]
+        };
+
+        // every vertex carries its normal through clipping so the new vertices get an interpolated one
+        let clipped = clip_polygon(
+            vec![
+                (view_polygon[0], vertex_normals[0]),
+                (view_polygon[1], vertex_normals[1]),
+                (view_polygon[2], vertex_normals[2]),
+            ],
+            &planes,
+            |vertex| vertex.0,
+            |a, b, t| (lerp_point(&a.0, &b.0, t), lerp_vectors(&a.1, &b.1, t)),
+        );
+
+        // whatever is left is convex so it can be drawn as a fan of triangles
+        for i in 1..clipped.len().saturating_sub(1) {
+            if let Some(triangle) = prepare_triangle(picture, &[clipped[0], clipped[i], clipped[i + 1]], shader, uniforms) {
+                triangles.push(triangle);
+            }
         }
-        _ => {}
     }
 
-    for polygon in m.chunks(3) {
-        let a = [
-            polygon[1][0] - polygon[0][0],
-            polygon[1][1] - polygon[0][1],
-            polygon[1][2] - polygon[0][2],
-        ];
-
-        let b = [
-            polygon[2][0] - polygon[0][0],
-            polygon[2][1] - polygon[0][1],
-            polygon[2][2] - polygon[0][2],
-        ];
-
-        // calculate the normal for backface culling using the cross product of two edges
-        // normal = < aybz - azby, azbx - axbz, axby - aybx >
-        let normal: Vector = [
-            a[1] * b[2] - a[2] * b[1],
-            a[2] * b[0] - a[0] * b[2],
-            a[0] * b[1] - a[1] * b[0],
-        ];
-
-        /*
-            if the angle between the normal and the viewer is between -90 and 90, the polygon is facing the viewer
-            we can find the angle between the normal and the viewer using this formula
-            |n||v|cos(theta) = dot product of n and v
-            we can use the fact that cos() will be (+) for the angle we need
-            |n||v| will always be (+) so we can just see if the dot product of n and v is (+) to see if cos is (+)
-            we will set v to <0, 0, 1> so the magnitude and dot products are easy to compute
-            the dot product of n and v is just the z component of n
-        */
-
-        if normal[2] > 0.0 && ENABLE_BACK_FACE_CULLING {
-            match shading_mode {
-                ShadingMode::Wireframe => {
-                    picture.draw_line(
-                        polygon[0][0] as isize, polygon[0][1] as isize, polygon[0][2],
-                        polygon[1][0] as isize, polygon[1][1] as isize, polygon[1][2],
-                        &color,
-                    );
-                    picture.draw_line(
-                        polygon[2][0] as isize, polygon[2][1] as isize, polygon[2][2],
-                        polygon[1][0] as isize, polygon[1][1] as isize, polygon[1][2],
-                        &color,
-                    );
-                    picture.draw_line(
-                        polygon[0][0] as isize, polygon[0][1] as isize, polygon[0][2],
-                        polygon[2][0] as isize, polygon[2][1] as isize, polygon[2][2],
-                        &color,
-                    );
-                },
-                ShadingMode::FlatRandom => {
-                    let mut rng = rand::rng();
-                    scan_line::flat(
-                        picture,
-                        polygon,
-                        &(rng.random::<u8>() as usize, rng.random::<u8>() as usize, rng.random::<u8>() as usize)
-                    );
-                },
-                ShadingMode::Flat => {
-                    scan_line::flat(
-                        picture,
-                        polygon,
-                        &get_illumination(&normalize_vector(&normal), lighting_config, reflection_constants)
-                    );
-                },
-                ShadingMode::Gouraud => {
-                    let normals = [
-                        *vertex_normals.get(&vector_to_key(&polygon[0])).unwrap(),
-                        *vertex_normals.get(&vector_to_key(&polygon[1])).unwrap(),
-                        *vertex_normals.get(&vector_to_key(&polygon[2])).unwrap(),
-                    ];
-
-                    scan_line::gouraud(picture, polygon, normals, lighting_config, reflection_constants);
-                }
-                ShadingMode::Phong => {
-                    let normals = [
-                        *vertex_normals.get(&vector_to_key(&polygon[0])).unwrap(),
-                        *vertex_normals.get(&vector_to_key(&polygon[1])).unwrap(),
-                        *vertex_normals.get(&vector_to_key(&polygon[2])).unwrap(),
-                    ];
-
-                    scan_line::phong(picture, polygon, normals, lighting_config, reflection_constants);
+    triangles
+}
+
+// each vertex is its position and normal in view space
+// the normals are the face normal unless the shader wants smooth ones
+// None if the triangle is facing away
+fn prepare_triangle(
+    picture: &Picture, triangle: &[([f32; 4], Vector); 3], shader: Option<&dyn Shader>, uniforms: &Uniforms,
+) -> Option<ScreenTriangle> {
+    let projection = uniforms.projection;
+    let polygon = triangle.map(|vertex| projection.project_point(&vertex.0, &picture.viewport, picture.xres, picture.yres));
+
+    let a = [
+        polygon[1][0] - polygon[0][0],
+        polygon[1][1] - polygon[0][1],
+        polygon[1][2] - polygon[0][2],
+    ];
+
+    let b = [
+        polygon[2][0] - polygon[0][0],
+        polygon[2][1] - polygon[0][1],
+        polygon[2][2] - polygon[0][2],
+    ];
+
+    // calculate the normal for backface culling using the cross product of two edges
+    let normal = cross_product(&a, &b);
+
+    /*
+        if the angle between the normal and the viewer is between -90 and 90, the polygon is facing the viewer
+        we can find the angle between the normal and the viewer using this formula
+        |n||v|cos(theta) = dot product of n and v
+        we can use the fact that cos() will be (+) for the angle we need
+        |n||v| will always be (+) so we can just see if the dot product of n and v is (+) to see if cos is (+)
+        we will set v to <0, 0, 1> so the magnitude and dot products are easy to compute
+        the dot product of n and v is just the z component of n
+    */
+
+    if normal[2] <= 0.0 || !ENABLE_BACK_FACE_CULLING {
+        return None;
+    }
+
+    let varyings = shader.map(|shader| {
+        let vertices = triangle.map(|(position, normal)| Vertex { position, normal, eye: projection.view_vector(&position) });
+        shader.vertex(&vertices, uniforms)
+    });
+
+    Some(ScreenTriangle { polygon, varyings })
+}
+
+/*
+    lots of triangles get drawn on several threads at once by splitting the picture into bands of rows (tiles)
+    every triangle goes into the bin of each tile it might touch, then each tile draws its bin in the original order
+    a pixel only ever belongs to one tile and gets the same triangles in the same order as drawing everything at once,
+    so the picture comes out exactly the same as it would on one thread
+*/
+fn draw_triangles(picture: &mut Picture, triangles: &[ScreenTriangle], shader: Option<&dyn Shader>, uniforms: &Uniforms) {
+    // each frame being drawn at the same time gets its share of the cores, so --jobs doesn't start jobs * cores threads
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()) / FRAME_JOBS.load(Ordering::Relaxed);
+
+    draw_triangles_on(picture, triangles, shader, uniforms, threads);
+}
+
+fn draw_triangles_on(picture: &mut Picture, triangles: &[ScreenTriangle], shader: Option<&dyn Shader>, uniforms: &Uniforms, threads: usize) {
+    if triangles.len() < PARALLEL_TRIANGLES || threads <= 1 {
+        let mut tile = picture.whole();
+        for triangle in triangles {
+            draw_triangle(&mut tile, triangle, shader, uniforms);
+        }
+        return;
+    }
+
+    let yres = picture.yres as isize;
+    // wide lines for wireframe reach past the corners
+    let slack = (picture.scaled_line_style().width / 2.0).ceil() as isize + 1;
+    let tiles = picture.tiles(TILE_ROWS);
+
+    let mut bins: Vec<Vec<&ScreenTriangle>> = tiles.iter().map(|_| vec![]).collect();
+    for triangle in triangles {
+        let y_values = triangle.polygon.map(|point| point[1]);
+        // some slack on each side since lines and the fill round differently
+        let bottom = (y_values[0].min(y_values[1]).min(y_values[2]).floor() as isize - slack).max(0);
+        let top = (y_values[0].max(y_values[1]).max(y_values[2]).ceil() as isize + slack).min(yres - 1);
+
+        if bottom > top {
+            continue;
+        }
+
+        // rows count down from the top of the picture
+        let first_tile = (yres - 1 - top) as usize / TILE_ROWS;
+        let last_tile = (yres - 1 - bottom) as usize / TILE_ROWS;
+        for bin in &mut bins[first_tile..=last_tile] {
+            bin.push(triangle);
+        }
+    }
+
+    // hand the tiles out every nth one so the busy middle of the picture gets split between threads
+    let threads = threads.min(tiles.len());
+    let mut jobs: Vec<Vec<(Tile, Vec<&ScreenTriangle>)>> = (0..threads).map(|_| vec![]).collect();
+    for (i, job) in tiles.into_iter().zip(bins).enumerate() {
+        jobs[i % threads].push(job);
+    }
+
+    thread::scope(|scope| {
+        for job in jobs {
+            scope.spawn(move || {
+                for (mut tile, bin) in job {
+                    for triangle in bin {
+                        draw_triangle(&mut tile, triangle, shader, uniforms);
+                    }
                 }
-            }
+            });
+        }
+    });
+}
+
+fn draw_triangle(tile: &mut Tile, triangle: &ScreenTriangle, shader: Option<&dyn Shader>, uniforms: &Uniforms) {
+    let polygon = &triangle.polygon;
+    tile.set_depth_slope(triangle.depth_slope());
+
+    match (shader, &triangle.varyings) {
+        (Some(shader), Some(varyings)) => {
+            scan_line::fill(tile, polygon, varyings, shader, uniforms);
+        }
+        _ => {
+            tile.draw_line(&polygon[0], &polygon[1], uniforms.color);
+            tile.draw_line(&polygon[2], &polygon[1], uniforms.color);
+            tile.draw_line(&polygon[0], &polygon[2], uniforms.color);
         }
     }
 }
@@ -322,3 +468,41 @@ fn generate_torus_points(cx: f32, cy: f32, cz: f32, r1: f32, r2: f32) -> Vec<Vec
 
     point_list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{DEFAULT_BACKGROUND_COLOR, DEFAULT_LIGHTING_CONFIG, DEFAULT_REFLECTION_CONSTANTS},
+        matrix,
+        render::shader::Phong,
+    };
+
+    #[test]
+    fn tiles_on_several_threads_draw_the_same_as_one() {
+        let projection = Projection::Orthographic;
+        let lighting_config = DEFAULT_LIGHTING_CONFIG;
+        let reflection_constants = DEFAULT_REFLECTION_CONSTANTS;
+        let uniforms = Uniforms { color: &(255, 160, 0), lighting_config: &lighting_config, reflection_constants: &reflection_constants, projection: &projection };
+
+        // two spheres poking through each other so the depth test matters where the tiles meet
+        let mut m = vec![];
+        add_sphere(&mut m, 220.0, 250.0, 0.0, 150.0);
+        add_sphere(&mut m, 300.0, 230.0, 40.0, 120.0);
+
+        let mut serial = Picture::new(500, 500, 255, &DEFAULT_BACKGROUND_COLOR);
+        let mut tiled = Picture::new(500, 500, 255, &DEFAULT_BACKGROUND_COLOR);
+        matrix::multiply(&projection.default_camera(&serial.viewport), &mut m);
+
+        // filled and then the edges on top, the same as hidden_line
+        for shader in [Some(&Phong as &dyn Shader), None] {
+            let triangles = screen_triangles(&m, &serial, shader, &uniforms);
+            assert!(triangles.len() >= PARALLEL_TRIANGLES);
+
+            draw_triangles_on(&mut serial, &triangles, shader, &uniforms, 1);
+            draw_triangles_on(&mut tiled, &triangles, shader, &uniforms, 4);
+
+            assert!(tiled.data == serial.data);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::f32::consts::PI;
+
+use crate::{
+    matrix,
+    vector::normalize_vector,
+};
+
+type Matrix = Vec<[f32; 4]>;
+type Vector = [f32; 3];
+
+// how much bigger than the picture the x and y clipping planes are
+const GUARD_BAND: f32 = 3.0;
+// parallel projections don't need an eye to work, but the near and far planes have to be measured from somewhere
+// the default camera puts it this far in front of the z = 0 plane, and the far plane is as far again behind it
+pub const PARALLEL_EYE_DISTANCE: f32 = 10000.0;
+
+/*
+    everything gets drawn in view space (after the camera transform) where the eye looks down -z
+    the projection turns a point in view space into a pixel position plus a depth for the z buffer
+
+    the eye is always at the origin of view space, even for orthographic and oblique where it only decides what gets clipped
+    the depth is always the view space z value, so bigger values are closer to the eye no matter the projection
+    the last component of a projected point is 1 / w, which is 1 for orthographic and 1 / distance for perspective
+*/
+
+#[derive(Clone, Copy, Debug)]
+pub enum Projection {
+    // x and y are measured from the middle of the viewport, with one unit being one unit of the viewport
+    Orthographic,
+    // fov is the vertical field of view in degrees
+    Perspective { fov: f32, near: f32, far: f32 },
+    // orthographic, but things further away get pushed along a line at angle degrees from the x axis
+    // depth_scale is how long that line is compared to the actual depth: 0.5 for cabinet, 1 for cavalier
+    Oblique { angle: f32, depth_scale: f32 },
+}
+
+impl Projection {
+    // distance from the eye where one world unit is one unit of the viewport, which is one pixel by default
+    pub fn focal_length(fov: f32, viewport: &Viewport) -> f32 {
+        (viewport.height() / 2.0) / (fov * PI / 360.0).tan()
+    }
+
+    // how much x and y move for every unit further away, only oblique has any
+    pub fn shear(&self) -> (f32, f32) {
+        match *self {
+            Projection::Oblique { angle, depth_scale } => {
+                let theta = angle * PI / 180.0;
+                (depth_scale * theta.cos(), depth_scale * theta.sin())
+            }
+            _ => (0.0, 0.0),
+        }
+    }
+
+    // used when the script never sets a camera
+    pub fn default_camera(&self, viewport: &Viewport) -> Matrix {
+        match *self {
+            Projection::Orthographic | Projection::Oblique { .. } => {
+                // in front of the middle of the viewport, and straight back along the direction things get projected in
+                // so scripts drawn in pixel coordinates still line up
+                let (kx, ky) = self.shear();
+                let (cx, cy) = viewport.center();
+                matrix::translation(-cx - kx * PARALLEL_EYE_DISTANCE, -cy - ky * PARALLEL_EYE_DISTANCE, -PARALLEL_EYE_DISTANCE)
+            }
+            Projection::Perspective { fov, .. } => {
+                // put the eye in front of the middle of the viewport at the distance where the z = 0 plane
+                // fills it exactly, so scripts drawn in pixel coordinates still line up
+                let distance = Projection::focal_length(fov, viewport);
+                let (cx, cy) = viewport.center();
+                matrix::translation(-cx, -cy, -distance)
+            }
+        }
+    }
+
+    pub fn project_point(&self, point: &[f32; 4], viewport: &Viewport, xres: usize, yres: usize) -> [f32; 4] {
+        match *self {
+            Projection::Orthographic => {
+                let (cx, cy) = viewport.center();
+                let (x, y) = viewport.pixel_position(cx + point[0], cy + point[1], xres, yres);
+                [x, y, point[2], 1.0]
+            }
+            Projection::Oblique { .. } => {
+                // further away is -z so this pushes things up and to the right for angles under 90
+                let (kx, ky) = self.shear();
+                let (cx, cy) = viewport.center();
+                let (x, y) = viewport.pixel_position(cx + point[0] - kx * point[2], cy + point[1] - ky * point[2], xres, yres);
+                [x, y, point[2], 1.0]
+            }
+            Projection::Perspective { fov, .. } => {
+                // perspective divide by the distance along the viewing direction
+                let focal_length = Projection::focal_length(fov, viewport);
+                let inverse_w = 1.0 / -point[2];
+                let (cx, cy) = viewport.center();
+
+                let (x, y) = viewport.pixel_position(
+                    cx + point[0] * focal_length * inverse_w,
+                    cy + point[1] * focal_length * inverse_w,
+                    xres, yres,
+                );
+
+                [x, y, point[2], inverse_w]
+            }
+        }
+    }
+
+    // direction from a point in view space to the eye, for specular highlights
+    pub fn view_vector(&self, point: &[f32; 4]) -> Vector {
+        match *self {
+            Projection::Orthographic => [0.0, 0.0, 1.0],
+            Projection::Perspective { .. } => normalize_vector(&[-point[0], -point[1], -point[2]]),
+            // every point along this direction lands on the same pixel
+            Projection::Oblique { .. } => {
+                let (kx, ky) = self.shear();
+                normalize_vector(&[kx, ky, 1.0])
+            }
+        }
+    }
+
+    // the planes that make up the view volume, in view space, see clipping.rs
+    pub fn clip_planes(&self, viewport: &Viewport) -> Vec<[f32; 4]> {
+        // x and y get clipped to a guard band around the viewport instead of its exact edges
+        // anything between the two gets thrown out per pixel by plot anyway,
+        // and this way triangles that are only a little offscreen get drawn exactly like before
+        let half_width = viewport.width() / 2.0 * GUARD_BAND;
+        let half_height = viewport.height() / 2.0 * GUARD_BAND;
+
+        // nothing behind the eye or too far in front of it, for every projection
+        let (near, far) = match *self {
+            Projection::Perspective { near, far, .. } => (near, far),
+            _ => (0.0, 2.0 * PARALLEL_EYE_DISTANCE),
+        };
+        let mut planes = vec![
+            [0.0, 0.0, -1.0, -near],
+            [0.0, 0.0, 1.0, far],
+        ];
+
+        match *self {
+            Projection::Orthographic => planes.extend([
+                [1.0, 0.0, 0.0, half_width],
+                [-1.0, 0.0, 0.0, half_width],
+                [0.0, 1.0, 0.0, half_height],
+                [0.0, -1.0, 0.0, half_height],
+            ]),
+            Projection::Oblique { .. } => {
+                // same as orthographic but the sides lean by the shear
+                let (kx, ky) = self.shear();
+
+                planes.extend([
+                    [1.0, 0.0, -kx, half_width],
+                    [-1.0, 0.0, kx, half_width],
+                    [0.0, 1.0, -ky, half_height],
+                    [0.0, -1.0, ky, half_height],
+                ]);
+            }
+            Projection::Perspective { fov, .. } => {
+                // the sides go through the eye so they don't have a constant term:
+                // -half_width <= x * focal_length / w <= half_width where w = -z
+                let focal_length = Projection::focal_length(fov, viewport);
+
+                planes.extend([
+                    [focal_length, 0.0, -half_width, 0.0],
+                    [-focal_length, 0.0, -half_width, 0.0],
+                    [0.0, focal_length, -half_height, 0.0],
+                    [0.0, -focal_length, -half_height, 0.0],
+                ]);
+            }
+        }
+
+        planes
+    }
+
+    pub fn project(&self, m: &Matrix, viewport: &Viewport, xres: usize, yres: usize) -> Matrix {
+        m.iter().map(|point| self.project_point(point, viewport, xres, yres)).collect()
+    }
+}
+
+/*
+    the viewport is the rectangle of world units (after the camera) that gets stretched over the whole picture
+    by default it's 0 0 500 500 no matter how big the picture is, so a script written for 500x500
+    draws the same thing at any resolution
+*/
+
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub xmin: f32,
+    pub ymin: f32,
+    pub xmax: f32,
+    pub ymax: f32,
+}
+
+impl Viewport {
+    pub fn width(&self) -> f32 {
+        self.xmax - self.xmin
+    }
+
+    pub fn height(&self) -> f32 {
+        self.ymax - self.ymin
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        ((self.xmin + self.xmax) / 2.0, (self.ymin + self.ymax) / 2.0)
+    }
+
+    // grows the viewport around its center until it has the same shape as an xres by yres picture
+    pub fn fit_aspect(&self, xres: usize, yres: usize) -> Viewport {
+        let aspect = xres as f32 / yres as f32;
+        let (cx, cy) = self.center();
+        let half_width = self.width().max(self.height() * aspect) / 2.0;
+        let half_height = self.height().max(self.width() / aspect) / 2.0;
+
+        Viewport { xmin: cx - half_width, ymin: cy - half_height, xmax: cx + half_width, ymax: cy + half_height }
+    }
+
+    pub fn pixel_position(&self, x: f32, y: f32, xres: usize, yres: usize) -> (f32, f32) {
+        (
+            (x - self.xmin) * (xres as f32 / self.width()),
+            (y - self.ymin) * (yres as f32 / self.height()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::clipping::is_inside;
+
+    const VIEWPORT: Viewport = Viewport { xmin: 0.0, ymin: 0.0, xmax: 500.0, ymax: 500.0 };
+    const PERSPECTIVE: Projection = Projection::Perspective { fov: 60.0, near: 1.0, far: 1000.0 };
+
+    #[test]
+    fn every_projection_clips_behind_the_eye_and_past_far() {
+        let projections = [
+            (Projection::Orthographic, 2.0 * PARALLEL_EYE_DISTANCE),
+            (Projection::Oblique { angle: 45.0, depth_scale: 0.5 }, 2.0 * PARALLEL_EYE_DISTANCE),
+            (PERSPECTIVE, 1000.0),
+        ];
+
+        for (projection, far) in projections {
+            let planes = projection.clip_planes(&VIEWPORT);
+            // straight ahead of the eye, which oblique pushes over by the shear
+            let (kx, ky) = projection.shear();
+            let ahead = |z: f32| [kx * z, ky * z, z, 1.0];
+
+            assert!(is_inside(&planes, &ahead(-far / 2.0)), "{:?}", projection);
+            assert!(!is_inside(&planes, &ahead(1.0)), "{:?}", projection);
+            assert!(!is_inside(&planes, &ahead(-far - 1.0)), "{:?}", projection);
+        }
+    }
+
+    #[test]
+    fn orthographic_sides_are_the_guard_band() {
+        let planes = Projection::Orthographic.clip_planes(&VIEWPORT);
+        let edge = 250.0 * GUARD_BAND;
+
+        for z in [-1.0, -PARALLEL_EYE_DISTANCE] {
+            assert!(is_inside(&planes, &[edge - 1.0, 0.0, z, 1.0]));
+            assert!(is_inside(&planes, &[0.0, -edge + 1.0, z, 1.0]));
+            assert!(!is_inside(&planes, &[edge + 1.0, 0.0, z, 1.0]));
+            assert!(!is_inside(&planes, &[0.0, -edge - 1.0, z, 1.0]));
+        }
+    }
+
+    #[test]
+    fn oblique_sides_lean_with_the_shear() {
+        let projection = Projection::Oblique { angle: 45.0, depth_scale: 1.0 };
+        let planes = projection.clip_planes(&VIEWPORT);
+        let (kx, ky) = projection.shear();
+        let edge = 250.0 * GUARD_BAND;
+        let z = -2000.0;
+
+        // this far back the middle of the picture is well past where orthographic would cut off
+        assert!(is_inside(&planes, &[kx * z, ky * z, z, 1.0]));
+        assert!(is_inside(&planes, &[kx * z + edge - 1.0, ky * z, z, 1.0]));
+        assert!(!is_inside(&planes, &[kx * z + edge + 1.0, ky * z, z, 1.0]));
+        assert!(!is_inside(&planes, &[0.0, 0.0, z, 1.0]));
+    }
+
+    #[test]
+    fn perspective_sides_go_through_the_eye() {
+        let planes = PERSPECTIVE.clip_planes(&VIEWPORT);
+        let focal_length = Projection::focal_length(60.0, &VIEWPORT);
+
+        for distance in [10.0, 500.0] {
+            // how far off to the side the edge of the guard band is at this distance
+            let edge = 250.0 * GUARD_BAND * distance / focal_length;
+
+            assert!(is_inside(&planes, &[edge * 0.99, 0.0, -distance, 1.0]));
+            assert!(is_inside(&planes, &[0.0, -edge * 0.99, -distance, 1.0]));
+            assert!(!is_inside(&planes, &[edge * 1.01, 0.0, -distance, 1.0]));
+            assert!(!is_inside(&planes, &[0.0, -edge * 1.01, -distance, 1.0]));
+        }
+    }
+}
@@ -0,0 +1,196 @@
+// 8 bits of sub pixel precision, vertices get snapped to 1/256 of a pixel
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_SCALE: f32 = (1 << SUBPIXEL_BITS) as f32;
+const ONE_PIXEL: i64 = 1 << SUBPIXEL_BITS;
+const HALF_PIXEL: i64 = ONE_PIXEL / 2;
+
+/*
+    edge functions tell which side of an edge a point is on:
+
+    E(a, b, p) = (bx - ax) * (py - ay) - (by - ay) * (px - ax)
+
+    it's positive on the left of a -> b, so for a counter clockwise triangle a pixel center is inside when
+    all three edge functions are positive, and the three values divided by the area are the barycentric weights
+
+    everything is done in integers after snapping, so two triangles sharing an edge get the exact same numbers
+    for it (just with opposite signs) and there's no floating point error to open up gaps between them
+    it also means a pixel comes out exactly the same no matter which part of the picture is being drawn,
+    which is what lets tiles be drawn on their own threads
+
+    pixels whose center lands exactly on an edge would be drawn by both triangles (or neither),
+    so the top left rule breaks the tie: the pixel belongs to the triangle for which that edge is a top or a left edge
+
+    in perspective, going the same number of pixels doesn't mean going the same distance on the actual triangle
+    (the far half of a floor takes up less of the picture than the near half), so the weights are off on screen
+    what does blend correctly on screen is anything divided by w, so each weight gets multiplied by its vertex's 1/w
+    and then they're all scaled back to add up to 1, which is the same as blending a/w and 1/w and dividing them
+    orthographic and oblique have w = 1 everywhere so nothing changes there
+*/
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// y goes up in the picture, so counter clockwise left edges go down and top edges go left
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    dy < 0 || (dy == 0 && dx < 0)
+}
+
+fn snap(point: &[f32; 4]) -> (i64, i64) {
+    ((point[0] * SUBPIXEL_SCALE).round() as i64, (point[1] * SUBPIXEL_SCALE).round() as i64)
+}
+
+// calls fragment with every pixel whose center is inside the triangle and the barycentric weights of its three vertices
+// the triangle is in pixels with 1/w in the last spot like project_point gives back, the weights are perspective correct
+// bounds are the smallest and biggest x and y of the pixels that can be drawn, everything else gets skipped
+pub fn rasterize<F>(triangle: &[[f32; 4]], bounds: (isize, isize, isize, isize), mut fragment: F)
+where
+    F: FnMut(isize, isize, [f32; 3]),
+{
+    let mut v = [snap(&triangle[0]), snap(&triangle[1]), snap(&triangle[2])];
+    let inverse_w = [triangle[0][3], triangle[1][3], triangle[2][3]];
+    let mut order = [0, 1, 2];
+
+    let mut area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return;
+    }
+    // either winding can show up, so flip clockwise triangles around and remember where the weights go
+    if area < 0 {
+        v.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+
+    // bounding box in whole pixels, only as much of it as is inside the bounds
+    let min_x = ((v[0].0.min(v[1].0).min(v[2].0) - HALF_PIXEL) >> SUBPIXEL_BITS).max(bounds.0 as i64);
+    let min_y = ((v[0].1.min(v[1].1).min(v[2].1) - HALF_PIXEL) >> SUBPIXEL_BITS).max(bounds.1 as i64);
+    let max_x = ((v[0].0.max(v[1].0).max(v[2].0) - HALF_PIXEL) >> SUBPIXEL_BITS).min(bounds.2 as i64);
+    let max_y = ((v[0].1.max(v[1].1).max(v[2].1) - HALF_PIXEL) >> SUBPIXEL_BITS).min(bounds.3 as i64);
+
+    if min_x > max_x || min_y > max_y {
+        return;
+    }
+
+    // edge i is the one across from vertex i, so its value is the weight of vertex i
+    let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+    // pixels right on an edge that isn't top left are outside
+    let bias = edges.map(|(a, b)| if is_top_left(a, b) { 0 } else { -1 });
+    // how much each edge function changes going one pixel right or up
+    let step_x = edges.map(|(a, b)| -(b.1 - a.1) * ONE_PIXEL);
+    let step_y = edges.map(|(a, b)| (b.0 - a.0) * ONE_PIXEL);
+
+    let start = ((min_x << SUBPIXEL_BITS) + HALF_PIXEL, (min_y << SUBPIXEL_BITS) + HALF_PIXEL);
+    let mut row = edges.map(|(a, b)| edge(a, b, start));
+
+    for y in min_y..=max_y {
+        let mut w = row;
+
+        for x in min_x..=max_x {
+            if w[0] + bias[0] >= 0 && w[1] + bias[1] >= 0 && w[2] + bias[2] >= 0 {
+                let mut weights = [0.0; 3];
+                for (i, &vertex) in order.iter().enumerate() {
+                    weights[vertex] = w[i] as f32 / area as f32 * inverse_w[vertex];
+                }
+                let total = weights[0] + weights[1] + weights[2];
+                for weight in &mut weights {
+                    *weight /= total;
+                }
+
+                fragment(x as isize, y as isize, weights);
+            }
+
+            for (w, step) in w.iter_mut().zip(step_x) {
+                *w += step;
+            }
+        }
+
+        for (w, step) in row.iter_mut().zip(step_y) {
+            *w += step;
+        }
+    }
+}
+
+// blends three per vertex values with barycentric weights
+pub fn interpolate(weights: &[f32; 3], values: [f32; 3]) -> f32 {
+    weights[0] * values[0] + weights[1] * values[1] + weights[2] * values[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: (isize, isize, isize, isize) = (0, 0, 63, 63);
+
+    fn covered(triangle: &[[f32; 4]]) -> Vec<(isize, isize)> {
+        let mut pixels = vec![];
+        rasterize(triangle, BOUNDS, |x, y, _| pixels.push((x, y)));
+        pixels
+    }
+
+    fn point(x: f32, y: f32) -> [f32; 4] {
+        [x, y, 0.0, 1.0]
+    }
+
+    #[test]
+    fn triangles_sharing_edges_cover_every_pixel_once() {
+        // corners on pixel centers so whole rows and columns land exactly on the edges, diagonals included
+        let corners = [
+            point(4.5, 4.5), point(12.5, 4.5), point(20.5, 4.5),
+            point(4.5, 12.5), point(12.5, 12.5), point(20.5, 12.5),
+        ];
+        let triangles = [
+            [corners[0], corners[1], corners[4]],
+            [corners[0], corners[4], corners[3]],
+            [corners[1], corners[2], corners[5]],
+            [corners[1], corners[5], corners[4]],
+            // same square split the other way and wound clockwise, stacked on top
+            [corners[3], point(4.5, 20.5), corners[4]],
+            [corners[4], point(4.5, 20.5), point(12.5, 20.5)],
+        ];
+
+        let mut counts = vec![0; 64 * 64];
+        for triangle in &triangles {
+            for (x, y) in covered(triangle) {
+                counts[y as usize * 64 + x as usize] += 1;
+            }
+        }
+
+        assert!(counts.iter().all(|&count| count <= 1));
+        // each of the three 8 by 8 squares gets exactly 64 pixels between its two triangles
+        assert_eq!(counts.iter().sum::<usize>(), 64 * 3);
+    }
+
+    #[test]
+    fn winding_doesnt_change_coverage() {
+        let (a, b, c) = (point(3.5, 2.0), point(17.25, 9.5), point(6.0, 21.5));
+
+        let mut counterclockwise = covered(&[a, b, c]);
+        let mut clockwise = covered(&[a, c, b]);
+        counterclockwise.sort();
+        clockwise.sort();
+
+        assert!(!counterclockwise.is_empty());
+        assert_eq!(counterclockwise, clockwise);
+    }
+
+    #[test]
+    fn weights_add_up_and_follow_the_vertices() {
+        let triangle = [point(0.5, 0.5), point(40.5, 0.5), point(0.5, 40.5)];
+        let mut checked = 0;
+
+        rasterize(&triangle, BOUNDS, |x, y, weights| {
+            assert!((weights[0] + weights[1] + weights[2] - 1.0).abs() < 1e-5);
+            assert!((weights[1] - x as f32 / 40.0).abs() < 1e-5);
+            assert!((weights[2] - y as f32 / 40.0).abs() < 1e-5);
+            checked += 1;
+        });
+
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn degenerate_triangles_draw_nothing() {
+        assert!(covered(&[point(1.0, 1.0), point(10.0, 10.0), point(20.0, 20.0)]).is_empty());
+    }
+}
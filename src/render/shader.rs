@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use rand::Rng;
+
+use crate::constants::{DEFAULT_BACKGROUND_COLOR, DEFAULT_EDGE_BIAS, DEFAULT_FOREGROUND_COLOR};
+use super::{
+    Projection,
+    lighting::{LightingConfig, ReflectionConstants, get_illumination},
+};
+
+type Vector = [f32; 3];
+
+// how many floats a shader can pass from its vertex stage to its fragment stage
+pub const MAX_VARYINGS: usize = 8;
+// whatever the vertex stage wants blended across the triangle, the fragment stage gets it back for every pixel
+pub type Varyings = [f32; MAX_VARYINGS];
+
+// one corner of a triangle, all in view space
+pub struct Vertex {
+    pub position: [f32; 4],
+    // the face normal, or the average of every face touching this corner if the shader wants smooth normals
+    pub normal: Vector,
+    // direction from the corner to the eye
+    pub eye: Vector,
+}
+
+// the same for every pixel of everything drawn by one command
+pub struct Uniforms<'a> {
+    pub color: &'a (usize, usize, usize),
+    pub lighting_config: &'a LightingConfig,
+    pub reflection_constants: &'a ReflectionConstants,
+    // for working out which way the eye is from somewhere other than a corner
+    pub projection: &'a Projection,
+}
+
+/*
+    a shader decides the color of every pixel of a polygon in two steps:
+    vertex gets called once for each triangle facing the camera, with all three corners so things like flat shading
+    can work on the whole face, and gives back the varyings for each corner
+    fragment gets called for every pixel the triangle covers, with the varyings blended (perspective correct) for that pixel
+
+    shaders get shared between the threads drawing a picture, so they can't hold onto anything mutable
+*/
+pub trait Shader: Debug + Send + Sync {
+    // whether vertex normals should be averaged over the faces around them instead of being the face normal
+    fn smooth_normals(&self) -> bool {
+        false
+    }
+
+    fn vertex(&self, triangle: &[Vertex; 3], uniforms: &Uniforms) -> [Varyings; 3];
+
+    fn fragment(&self, varyings: &Varyings, uniforms: &Uniforms) -> (usize, usize, usize);
+}
+
+#[derive(Clone, Debug)]
+pub enum ShadingMode {
+    // just the edges of every triangle, no shader involved, so edges behind things still show
+    Wireframe,
+    Shader(Arc<dyn Shader>),
+    // fills with the shader and then draws the edges on top in their own color
+    // bias pulls the edges towards the eye so they win against the faces they're on but not against anything in front
+    ShadedEdges { shader: Arc<dyn Shader>, color: (usize, usize, usize), bias: f32 },
+}
+
+impl ShadingMode {
+    // what the shading command picks for a name
+    pub fn named(name: &str) -> Option<ShadingMode> {
+        match name {
+            "wireframe" => Some(ShadingMode::Wireframe),
+            // filling with the background color hides every edge that's behind something
+            "hidden_line" => Some(ShadingMode::ShadedEdges {
+                shader: Arc::new(Solid(DEFAULT_BACKGROUND_COLOR)),
+                color: DEFAULT_FOREGROUND_COLOR,
+                bias: DEFAULT_EDGE_BIAS,
+            }),
+            _ => get(name).map(ShadingMode::Shader),
+        }
+    }
+}
+
+fn color_varyings(color: (usize, usize, usize)) -> Varyings {
+    let mut varyings = [0.0; MAX_VARYINGS];
+    varyings[..3].copy_from_slice(&[color.0 as f32, color.1 as f32, color.2 as f32]);
+    varyings
+}
+
+// the same color everywhere with no lighting
+#[derive(Debug)]
+pub struct Solid(pub (usize, usize, usize));
+
+impl Shader for Solid {
+    fn vertex(&self, _triangle: &[Vertex; 3], _uniforms: &Uniforms) -> [Varyings; 3] {
+        [[0.0; MAX_VARYINGS]; 3]
+    }
+
+    fn fragment(&self, _varyings: &Varyings, _uniforms: &Uniforms) -> (usize, usize, usize) {
+        self.0
+    }
+}
+
+// one color for the whole face, lit as seen from the middle of it
+#[derive(Debug)]
+pub struct Flat;
+
+impl Shader for Flat {
+    fn vertex(&self, triangle: &[Vertex; 3], uniforms: &Uniforms) -> [Varyings; 3] {
+        let center = [
+            (triangle[0].position[0] + triangle[1].position[0] + triangle[2].position[0]) / 3.0,
+            (triangle[0].position[1] + triangle[1].position[1] + triangle[2].position[1]) / 3.0,
+            (triangle[0].position[2] + triangle[1].position[2] + triangle[2].position[2]) / 3.0,
+            1.0,
+        ];
+        let eye = uniforms.projection.view_vector(&center);
+        let color = get_illumination(&triangle[0].normal, &eye, uniforms.lighting_config, uniforms.reflection_constants);
+
+        [color_varyings(color); 3]
+    }
+
+    fn fragment(&self, varyings: &Varyings, _uniforms: &Uniforms) -> (usize, usize, usize) {
+        // the three corners are the same so blending them only adds rounding error
+        (varyings[0].round() as usize, varyings[1].round() as usize, varyings[2].round() as usize)
+    }
+}
+
+// every face gets a random color, handy for seeing how something is split into triangles
+#[derive(Debug)]
+pub struct FlatRandom;
+
+impl Shader for FlatRandom {
+    fn vertex(&self, _triangle: &[Vertex; 3], _uniforms: &Uniforms) -> [Varyings; 3] {
+        let mut rng = rand::rng();
+        let color = (rng.random::<u8>() as usize, rng.random::<u8>() as usize, rng.random::<u8>() as usize);
+
+        [color_varyings(color); 3]
+    }
+
+    fn fragment(&self, varyings: &Varyings, uniforms: &Uniforms) -> (usize, usize, usize) {
+        Flat.fragment(varyings, uniforms)
+    }
+}
+
+// lights the corners and blends the colors in between
+#[derive(Debug)]
+pub struct Gouraud;
+
+impl Shader for Gouraud {
+    fn smooth_normals(&self) -> bool {
+        true
+    }
+
+    fn vertex(&self, triangle: &[Vertex; 3], uniforms: &Uniforms) -> [Varyings; 3] {
+        triangle.each_ref().map(|vertex| {
+            color_varyings(get_illumination(&vertex.normal, &vertex.eye, uniforms.lighting_config, uniforms.reflection_constants))
+        })
+    }
+
+    fn fragment(&self, varyings: &Varyings, _uniforms: &Uniforms) -> (usize, usize, usize) {
+        (
+            varyings[0].clamp(0.0, 255.0) as usize,
+            varyings[1].clamp(0.0, 255.0) as usize,
+            varyings[2].clamp(0.0, 255.0) as usize,
+        )
+    }
+}
+
+// blends the normals (and directions to the eye) and lights every pixel
+#[derive(Debug)]
+pub struct Phong;
+
+impl Shader for Phong {
+    fn smooth_normals(&self) -> bool {
+        true
+    }
+
+    fn vertex(&self, triangle: &[Vertex; 3], _uniforms: &Uniforms) -> [Varyings; 3] {
+        triangle.each_ref().map(|vertex| {
+            let mut varyings = [0.0; MAX_VARYINGS];
+            varyings[..3].copy_from_slice(&vertex.normal);
+            varyings[3..6].copy_from_slice(&vertex.eye);
+            varyings
+        })
+    }
+
+    fn fragment(&self, varyings: &Varyings, uniforms: &Uniforms) -> (usize, usize, usize) {
+        let normal = [varyings[0], varyings[1], varyings[2]];
+        let eye = [varyings[3], varyings[4], varyings[5]];
+
+        get_illumination(&normal, &eye, uniforms.lighting_config, uniforms.reflection_constants)
+    }
+}
+
+// every shader that comes with the renderer is there from the start, a new one only needs a line here
+static SHADERS: LazyLock<RwLock<HashMap<String, Arc<dyn Shader>>>> = LazyLock::new(|| {
+    let builtins: [(&str, Arc<dyn Shader>); 4] = [
+        ("flat", Arc::new(Flat)),
+        ("flat_random", Arc::new(FlatRandom)),
+        ("gouraud", Arc::new(Gouraud)),
+        ("phong", Arc::new(Phong)),
+    ];
+
+    RwLock::new(builtins.into_iter().map(|(name, shader)| (name.to_string(), shader)).collect())
+});
+
+// makes a shader available to the shading command under name, replacing whatever had that name before
+// has to happen before the script gets parsed, "wireframe" and "hidden_line" can't be replaced
+// there's no library target, so custom shaders get registered at the top of main (the renderer never calls this itself)
+#[allow(dead_code)]
+pub fn register(name: &str, shader: Arc<dyn Shader>) {
+    SHADERS.write().unwrap().insert(name.to_lowercase(), shader);
+}
+
+pub fn get(name: &str) -> Option<Arc<dyn Shader>> {
+    SHADERS.read().unwrap().get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_shaders_are_there_without_registering() {
+        for name in ["flat", "flat_random", "gouraud", "phong", "wireframe", "hidden_line"] {
+            assert!(ShadingMode::named(name).is_some(), "{}", name);
+        }
+        assert!(ShadingMode::named("toon").is_none());
+    }
+
+    #[test]
+    fn registered_shaders_can_be_picked_by_name() {
+        register("Test_Solid", Arc::new(Solid((1, 2, 3))));
+
+        match ShadingMode::named("test_solid") {
+            Some(ShadingMode::Shader(shader)) => assert_eq!(format!("{:?}", shader), "Solid((1, 2, 3))"),
+            other => panic!("{:?}", other),
+        }
+    }
+}
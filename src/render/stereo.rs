@@ -0,0 +1,77 @@
+use crate::matrix;
+
+use super::{Picture, Projection, projection::PARALLEL_EYE_DISTANCE};
+
+type Matrix = Vec<[f32; 4]>;
+
+#[derive(Clone, Copy, Debug)]
+pub enum StereoMode {
+    // left eye in the red channel, right eye in green and blue, for red-cyan glasses
+    Anaglyph,
+    // left eye on the left, right eye on the right, so the picture is twice as wide
+    SideBySide,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    pub eye_separation: f32,
+    // how far in front of the eyes they both point at, things at that distance line up in both pictures
+    pub convergence: f32,
+    pub mode: StereoMode,
+}
+
+impl Stereo {
+    /*
+        each eye is the normal view followed by one more look_at in view space:
+        the eye moves half the separation left or right and turns in to look at the convergence point
+
+        for perspective the eye sits at the origin of view space
+        orthographic and oblique only have an eye for clipping, so they converge where the default camera
+        sees the z = 0 plane (where scripts normally draw)
+    */
+    pub fn eye_view(&self, view_matrix: &Matrix, projection: &Projection, left: bool) -> Matrix {
+        let offset = if left { -self.eye_separation / 2.0 } else { self.eye_separation / 2.0 };
+
+        let z = match projection {
+            Projection::Perspective { .. } => 0.0,
+            _ => self.convergence - PARALLEL_EYE_DISTANCE,
+        };
+
+        let mut eye_view = view_matrix.clone();
+        matrix::multiply(&matrix::look_at(&[offset, 0.0, z], &[0.0, 0.0, z - self.convergence], &[0.0, 1.0, 0.0], 0.0), &mut eye_view);
+
+        // look_at moved the eye to the origin, orthographic and oblique want it back where it was
+        // so the near plane doesn't cut into the scene
+        if !matches!(projection, Projection::Perspective { .. }) {
+            matrix::multiply(&matrix::translation(0.0, 0.0, z), &mut eye_view);
+        }
+
+        eye_view
+    }
+
+    pub fn combine(&self, left: &Picture, right: &Picture) -> Picture {
+        match self.mode {
+            StereoMode::Anaglyph => {
+                let mut picture = Picture::new(left.xres, left.yres, 255, &(0, 0, 0));
+                picture.viewport = left.viewport;
+
+                for (i, pixel) in picture.data.chunks_mut(3).enumerate() {
+                    pixel[0] = left.data[i * 3];
+                    pixel[1] = right.data[i * 3 + 1];
+                    pixel[2] = right.data[i * 3 + 2];
+                }
+
+                picture
+            }
+            StereoMode::SideBySide => {
+                let mut picture = Picture::new(left.xres * 2, left.yres, 255, &(0, 0, 0));
+                picture.viewport = left.viewport;
+
+                picture.paste(left, 0, 0);
+                picture.paste(right, left.xres, 0);
+
+                picture
+            }
+        }
+    }
+}
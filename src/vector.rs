@@ -24,4 +24,8 @@ pub fn cross_product(a: &Vector, b: &Vector) -> Vector {
 
 pub fn dot_product(a: &Vector, b: &Vector) -> f32 {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
-}
\ No newline at end of file
+}
+
+pub fn lerp_vectors(a: &Vector, b: &Vector, t: f32) -> Vector {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}